@@ -1,31 +1,207 @@
 // Copyright 2021 Remi Bernotavicius
 
-use std::path::PathBuf;
+use bytesize::ByteSize;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::{io, net};
-use structopt::StructOpt;
+use structopt::{clap, StructOpt};
+use vdu::{DeleteMode, ExportFormat, ImportFormat};
+use vdu_path_tree::PathTree;
 
-#[derive(Debug, StructOpt)]
-struct Opt {
-    #[structopt(parse(from_os_str))]
-    path: PathBuf,
+mod tui;
 
+#[derive(Debug, StructOpt)]
+struct ServerOpt {
     #[structopt(long)]
     do_not_open_browser: bool,
 
     #[structopt(long, default_value = "localhost")]
     host: String,
+
+    /// Allow deleting entries inside the scanned paths from the web page, after confirmation
+    #[structopt(long)]
+    allow_delete: bool,
+
+    /// What deleting from the web page does, one of: trash, permanent
+    #[structopt(long, default_value = "trash")]
+    delete_mode: DeleteMode,
+
+    /// The access token the web page has to be opened with, instead of a random one. Letters,
+    /// digits and "-._~" only
+    #[structopt(long, parse(try_from_str = parse_token), conflicts_with = "no-token")]
+    token: Option<String>,
+
+    /// Let anyone who can connect to the server see the tree, without an access token
+    #[structopt(long)]
+    no_token: bool,
 }
 
-fn main() -> io::Result<()> {
-    simple_logger::SimpleLogger::new().init().unwrap();
+/// Access tokens go in the URL and a cookie as they are, so only characters that need no escaping
+/// in either are allowed.
+fn parse_token(token: &str) -> Result<String, String> {
+    if token.is_empty() {
+        return Err("the token can't be empty".into());
+    }
+    match token
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || "-._~".contains(c)))
+    {
+        Some(c) => Err(format!("the token can't contain {:?}", c)),
+        None => Ok(token.into()),
+    }
+}
 
-    let opt = Opt::from_args();
-    let tree = vdu::build_tree_from_path(&opt.path)?;
+#[derive(Debug, StructOpt)]
+struct Input {
+    /// The paths to scan, or listing files when --import is given. Several are combined into one
+    /// tree.
+    #[structopt(parse(from_os_str), required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Read <paths> as listings in this format (du, find, ncdu) instead of scanning them
+    #[structopt(long = "import")]
+    import_format: Option<ImportFormat>,
+}
+
+impl Input {
+    fn load(&self) -> io::Result<PathTree> {
+        if let Some(format) = self.import_format {
+            merge_trees(&self.paths, |file| import(format, file))
+        } else {
+            merge_trees(&self.paths, vdu::build_tree_from_path)
+        }
+    }
+
+    /// The paths the tree is scanned from, none when it is imported.
+    fn scan_roots(&self) -> Vec<PathBuf> {
+        match self.import_format {
+            Some(_) => vec![],
+            None => self.paths.clone(),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Write the tree out as an ncdu JSON export, CSV or nested JSON.
+    Export {
+        /// One of: ncdu, csv, json
+        #[structopt(long)]
+        format: ExportFormat,
+
+        /// Where to write the export, instead of stdout
+        #[structopt(long, short, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        #[structopt(flatten)]
+        input: Input,
+    },
+    /// Print the tree sorted by size, without starting a server.
+    Report {
+        /// How many levels below the root to show
+        #[structopt(long, default_value = "2")]
+        depth: usize,
+
+        /// Summarize entries smaller than this, e.g. "10 MiB"
+        #[structopt(long, default_value = "0")]
+        min_size: ByteSize,
+
+        #[structopt(flatten)]
+        input: Input,
+    },
+    /// Browse the tree in the terminal, one directory at a time.
+    Browse {
+        /// Allow deleting the selected entry with 'd', after confirmation. Not for imported
+        /// listings, whose paths may not be on this machine
+        #[structopt(long, conflicts_with = "import-format")]
+        allow_delete: bool,
 
-    let socket = net::TcpListener::bind(&format!("{}:0", opt.host))?;
+        #[structopt(flatten)]
+        input: Input,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// The paths to scan. Several are combined into one tree under their common ancestor.
+    #[structopt(parse(from_os_str))]
+    paths: Vec<PathBuf>,
+
+    /// Read <paths> as listings in this format (du, find, ncdu) instead of scanning them, like
+    /// ones produced by `du -ab`, `find -printf '%s %p\n'` or `ncdu -o`
+    #[structopt(long = "import")]
+    import_format: Option<ImportFormat>,
+
+    #[structopt(flatten)]
+    server: ServerOpt,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+fn import(format: ImportFormat, file: &Path) -> io::Result<PathTree> {
+    log::info!("importing \"{}\"", file.display());
+    let input = io::BufReader::new(File::open(file)?);
+    vdu::import_tree(format, input)
+}
+
+/// Loads each path and merges them, later paths winning where they overlap.
+fn merge_trees(
+    paths: &[PathBuf],
+    mut load: impl FnMut(&Path) -> io::Result<PathTree>,
+) -> io::Result<PathTree> {
+    let mut tree = PathTree::empty();
+    for path in paths {
+        tree.merge(load(path)?);
+    }
+    if paths.len() > 1 {
+        log::info!(
+            "combined {} trees, {} files in total",
+            paths.len(),
+            tree.size()
+        );
+    }
+    Ok(tree)
+}
+
+fn export(format: ExportFormat, tree: &PathTree, output: Option<&Path>) -> io::Result<()> {
+    let output: Box<dyn io::Write> = match output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    vdu::export_tree(format, tree, io::BufWriter::new(output))
+}
+
+/// Serves `tree`, which was scanned from `scan_roots`, or imported if there are none.
+fn serve(tree: PathTree, opt: &ServerOpt, scan_roots: Vec<PathBuf>) -> io::Result<()> {
+    if opt.allow_delete && scan_roots.is_empty() {
+        log::warn!("--allow-delete has no effect on imported trees");
+    }
+    let token = match (&opt.token, opt.no_token) {
+        (_, true) => None,
+        (Some(token), false) => Some(token.clone()),
+        (None, false) => Some(vdu::random_token()?),
+    };
+    if token.is_none() && opt.host != "localhost" {
+        log::warn!(
+            "--no-token lets anyone who can connect to {} see the tree",
+            opt.host
+        );
+    }
+    let options = vdu::ServerOptions {
+        scan_roots,
+        allow_delete: opt.allow_delete,
+        delete_mode: opt.delete_mode,
+        token: token.clone(),
+    };
+
+    let socket = net::TcpListener::bind(format!("{}:0", opt.host))?;
     let port = socket.local_addr()?.port();
 
-    let url = format!("http://{}:{}/", opt.host, port);
+    let mut url = format!("http://{}:{}/", opt.host, port);
+    if let Some(token) = &token {
+        url += &format!("?token={}", token);
+    }
     log::info!("visit {} to see results", url);
 
     if !opt.do_not_open_browser {
@@ -33,5 +209,47 @@ fn main() -> io::Result<()> {
         webbrowser::open(&url).unwrap();
     }
 
-    vdu::run_server(tree, socket)
+    vdu::run_server(tree, options, socket)
+}
+
+fn main() -> io::Result<()> {
+    simple_logger::SimpleLogger::new().init().unwrap();
+
+    let opt = Opt::from_args();
+    match opt.command {
+        Some(Command::Export {
+            format,
+            output,
+            input,
+        }) => export(format, &input.load()?, output.as_deref()),
+        Some(Command::Report {
+            depth,
+            min_size,
+            input,
+        }) => {
+            let options = vdu::ReportOptions {
+                max_depth: depth,
+                min_bytes: min_size.as_u64(),
+            };
+            vdu::write_report(&input.load()?, &options, io::stdout().lock())
+        }
+        Some(Command::Browse {
+            allow_delete,
+            input,
+        }) => tui::run(input.load()?, allow_delete, input.scan_roots()),
+        None => {
+            if opt.paths.is_empty() {
+                clap::Error::with_description(
+                    "a path to scan is required",
+                    clap::ErrorKind::MissingRequiredArgument,
+                )
+                .exit()
+            }
+            let input = Input {
+                paths: opt.paths,
+                import_format: opt.import_format,
+            };
+            serve(input.load()?, &opt.server, input.scan_roots())
+        }
+    }
 }
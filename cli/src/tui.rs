@@ -0,0 +1,347 @@
+// copyright 2021 Remi Bernotavicius
+
+use bytesize::ByteSize;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use vdu_path_tree::{PathTree, PathTreeNode};
+
+const BAR_WIDTH: usize = 10;
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    num_bytes: u64,
+    is_dir: bool,
+}
+
+/// What is being looked at, kept apart from the drawing so it can be tested.
+struct Browser {
+    tree: PathTree,
+    /// The paths the tree was scanned from; only what is inside them can be deleted.
+    scan_roots: Vec<PathBuf>,
+    /// The directories entered to get to the current one, with what was selected in each.
+    parents: Vec<(PathBuf, usize)>,
+    current: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+    scroll: usize,
+}
+
+impl Browser {
+    fn new(tree: PathTree, scan_roots: Vec<PathBuf>) -> Self {
+        let current = tree.root().map(|r| r.path().to_owned()).unwrap_or_default();
+        let mut browser = Self {
+            tree,
+            scan_roots,
+            parents: vec![],
+            current,
+            entries: vec![],
+            selected: 0,
+            scroll: 0,
+        };
+        browser.refresh();
+        browser
+    }
+
+    fn current_node(&self) -> Option<&PathTreeNode> {
+        self.tree.get(&self.current)
+    }
+
+    fn refresh(&mut self) {
+        let mut entries: Vec<_> = self
+            .current_node()
+            .into_iter()
+            .flat_map(|n| n.children())
+            .map(|(name, node)| Entry {
+                name: name.into(),
+                path: node.path().to_owned(),
+                num_bytes: node.num_bytes(),
+                is_dir: node.is_directory(),
+            })
+            .collect();
+        entries.sort_by(|a, b| b.num_bytes.cmp(&a.num_bytes).then(a.name.cmp(&b.name)));
+        self.entries = entries;
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    fn descend(&mut self) {
+        if let Some(entry) = self.selected().filter(|e| e.is_dir) {
+            let path = entry.path.clone();
+            let current = std::mem::replace(&mut self.current, path);
+            self.parents.push((current, self.selected));
+            self.selected = 0;
+            self.scroll = 0;
+            self.refresh();
+        }
+    }
+
+    fn ascend(&mut self) {
+        if let Some((parent, selected)) = self.parents.pop() {
+            self.current = parent;
+            self.selected = selected;
+            self.refresh();
+        }
+    }
+
+    fn delete_selected(&mut self) -> io::Result<()> {
+        if let Some(entry) = self.selected() {
+            let path = entry.path.clone();
+            if !vdu::is_inside(&self.scan_roots, &path) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "only what is inside the scanned paths can be deleted",
+                ));
+            }
+            vdu::delete_path(&mut self.tree, &path)?;
+            self.refresh();
+        }
+        Ok(())
+    }
+
+    /// Keeps the selection inside the `rows` visible lines.
+    fn scroll_to_selection(&mut self, rows: usize) {
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if rows > 0 && self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+    }
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
+
+fn entry_line(entry: &Entry, total: u64) -> String {
+    let fraction = if total == 0 {
+        0.0
+    } else {
+        entry.num_bytes as f64 / total as f64
+    };
+    let filled = ((fraction * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    format!(
+        "{:>10} {:>5.1}% [{}{}] {}{}",
+        ByteSize::b(entry.num_bytes).to_string(),
+        fraction * 100.0,
+        "#".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        entry.name,
+        if entry.is_dir { "/" } else { "" }
+    )
+}
+
+/// The line at the top, with the directory being looked at and what is in it.
+fn header(browser: &Browser) -> String {
+    let (total, inside) = browser
+        .current_node()
+        .map_or((0, 0), |n| (n.num_bytes(), n.size() - 1));
+    let inside = match inside {
+        1 => "1 entry inside".into(),
+        n => format!("{} entries inside (files and directories)", n),
+    };
+    format!(
+        " {}  {}, {}",
+        browser.current.display(),
+        ByteSize::b(total),
+        inside
+    )
+}
+
+fn draw(browser: &mut Browser, status: &str, out: &mut impl Write) -> io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let (width, height) = (width as usize, height as usize);
+    let rows = height.saturating_sub(2);
+    browser.scroll_to_selection(rows);
+
+    queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+
+    let total = browser.current_node().map_or(0, |n| n.num_bytes());
+    let header = header(browser);
+    queue!(
+        out,
+        SetAttribute(Attribute::Reverse),
+        Print(format!(
+            "{:<width$}",
+            truncate(&header, width),
+            width = width
+        )),
+        SetAttribute(Attribute::Reset)
+    )?;
+
+    let visible = browser.entries.iter().enumerate().skip(browser.scroll);
+    for (row, (i, entry)) in visible.take(rows).enumerate() {
+        queue!(out, cursor::MoveTo(0, row as u16 + 1))?;
+        let line = truncate(&entry_line(entry, total), width);
+        if i == browser.selected {
+            let line = format!("{:<width$}", line, width = width);
+            queue!(
+                out,
+                SetAttribute(Attribute::Reverse),
+                Print(line),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(out, Print(line))?;
+        }
+    }
+
+    queue!(
+        out,
+        cursor::MoveTo(0, height.saturating_sub(1) as u16),
+        Print(truncate(status, width))
+    )?;
+    out.flush()
+}
+
+const HELP: &str = "up/down: move  right/enter: open  left/backspace: parent  q: quit";
+const HELP_DELETE: &str =
+    "up/down: move  right/enter: open  left/backspace: parent  d: delete  q: quit";
+
+fn event_loop(browser: &mut Browser, allow_delete: bool, out: &mut impl Write) -> io::Result<()> {
+    let help = if allow_delete { HELP_DELETE } else { HELP };
+    let mut status = help.to_owned();
+    let mut confirming_delete = false;
+
+    loop {
+        draw(browser, &status, out)?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        if confirming_delete {
+            confirming_delete = false;
+            status = match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => match browser.delete_selected() {
+                    Ok(()) => "deleted".into(),
+                    Err(e) => format!("failed to delete: {}", e),
+                },
+                _ => help.into(),
+            };
+            continue;
+        }
+
+        let page = terminal::size()?.1.saturating_sub(2).max(1) as isize;
+        status = help.into();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Up | KeyCode::Char('k') => browser.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => browser.move_selection(1),
+            KeyCode::PageUp => browser.move_selection(-page),
+            KeyCode::PageDown => browser.move_selection(page),
+            KeyCode::Home => browser.move_selection(isize::MIN / 2),
+            KeyCode::End => browser.move_selection(isize::MAX / 2),
+            KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') => browser.descend(),
+            KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => browser.ascend(),
+            KeyCode::Char('d') if allow_delete => {
+                if let Some(entry) = browser.selected() {
+                    status = format!(
+                        "delete {} ({})? y/N",
+                        entry.path.display(),
+                        ByteSize::b(entry.num_bytes)
+                    );
+                    confirming_delete = true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Browses the tree in the terminal one directory at a time, largest entries first. The tree was
+/// scanned from `scan_roots`, or imported if there are none.
+pub fn run(tree: PathTree, allow_delete: bool, scan_roots: Vec<PathBuf>) -> io::Result<()> {
+    let mut browser = Browser::new(tree, scan_roots);
+    let mut stdout = io::stdout();
+
+    // Log lines would be written over the top of the screen.
+    let log_level = log::max_level();
+    log::set_max_level(log::LevelFilter::Off);
+
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = event_loop(&mut browser, allow_delete, &mut stdout);
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    log::set_max_level(log_level);
+    result
+}
+
+#[cfg(test)]
+fn test_browser() -> Browser {
+    use std::path::Path;
+    use vdu_path_tree::NodeMetadata;
+
+    let mut tree = PathTree::empty();
+    for &(path, num_bytes) in &[("/r", 0), ("/r/a", 1), ("/r/b", 0), ("/r/b/c", 5)] {
+        tree.add_path(Path::new(path), num_bytes, NodeMetadata::unknown());
+    }
+    Browser::new(tree, vec![])
+}
+
+#[test]
+fn browser_sorts_entries_by_size() {
+    let browser = test_browser();
+    let names: Vec<_> = browser.entries.iter().map(|e| &e.name[..]).collect();
+    assert_eq!(names, ["b", "a"]);
+}
+
+#[test]
+fn browser_returns_to_selection_in_parent() {
+    let mut browser = test_browser();
+    browser.descend();
+    assert_eq!(browser.current, PathBuf::from("/r/b"));
+    assert_eq!(browser.entries.len(), 1);
+
+    // files can't be entered
+    browser.descend();
+    assert_eq!(browser.current, PathBuf::from("/r/b"));
+
+    browser.ascend();
+    assert_eq!(browser.current, PathBuf::from("/r"));
+    assert_eq!(browser.selected().unwrap().name, "b");
+}
+
+#[test]
+fn browser_selection_stays_in_bounds() {
+    let mut browser = test_browser();
+    browser.move_selection(10);
+    assert_eq!(browser.selected, 1);
+    browser.move_selection(-10);
+    assert_eq!(browser.selected, 0);
+}
+
+#[test]
+fn browser_only_deletes_inside_scan_roots() {
+    // an imported tree has no scanned paths, so nothing in it is on this file-system to delete
+    let mut browser = test_browser();
+    let error = browser.delete_selected().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    assert_eq!(browser.entries.len(), 2);
+}
+
+#[test]
+fn header_counts_what_is_inside() {
+    let mut browser = test_browser();
+    assert_eq!(
+        header(&browser),
+        " /r  6 B, 3 entries inside (files and directories)"
+    );
+    browser.descend();
+    assert_eq!(header(&browser), " /r/b  5 B, 1 entry inside");
+}
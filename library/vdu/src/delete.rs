@@ -0,0 +1,117 @@
+// copyright 2021 Remi Bernotavicius
+
+use crate::trash::{move_to_trash, move_to_trash_with_home, Trashed};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use vdu_path_tree::PathTree;
+
+/// Deletes `path` from the file-system, recursively if it is a directory, and removes it from the
+/// tree so the totals reflect the deletion.
+pub fn delete_path(tree: &mut PathTree, path: &Path) -> io::Result<()> {
+    log::info!("deleting \"{}\"", path.display());
+
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    tree.remove_path(path);
+    Ok(())
+}
+
+/// What deleting from the web client does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeleteMode {
+    /// Moves things to the trash, from where they can be restored.
+    #[default]
+    Trash,
+    Permanent,
+}
+
+impl FromStr for DeleteMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trash" => Ok(Self::Trash),
+            "permanent" => Ok(Self::Permanent),
+            _ => Err(format!(
+                "unknown delete mode '{}', expected one of: trash, permanent",
+                s
+            )),
+        }
+    }
+}
+
+/// Something moved to the trash by `trash_path`, along with what was taken out of the tree for
+/// it.
+pub struct TrashedPath {
+    trashed: Trashed,
+    removed: PathTree,
+}
+
+impl TrashedPath {
+    pub fn path(&self) -> &Path {
+        self.trashed.path()
+    }
+
+    pub fn num_bytes(&self) -> u64 {
+        self.removed.num_bytes()
+    }
+
+    /// Moves it back out of the trash and puts it back in `tree`. It can't be restored again
+    /// after that.
+    pub fn restore(&mut self, tree: &mut PathTree) -> io::Result<()> {
+        log::info!("restoring \"{}\" from the trash", self.path().display());
+        self.trashed.restore()?;
+        tree.merge(std::mem::replace(&mut self.removed, PathTree::empty()));
+        Ok(())
+    }
+}
+
+/// Like `delete_path`, but moves `path` to the trash instead, with `home_trash` in place of the
+/// trash in the home directory if it is given.
+pub fn trash_path(
+    tree: &mut PathTree,
+    path: &Path,
+    home_trash: Option<&Path>,
+) -> io::Result<TrashedPath> {
+    log::info!("moving \"{}\" to the trash", path.display());
+
+    let trashed = match home_trash {
+        Some(home_trash) => move_to_trash_with_home(path, home_trash.to_owned())?,
+        None => move_to_trash(path)?,
+    };
+    let removed = tree.remove_path(path).unwrap_or_else(PathTree::empty);
+    Ok(TrashedPath { trashed, removed })
+}
+
+/// Whether `path` is inside one of `roots`, and not one of them itself. It goes by where `path`
+/// really is, so a directory on the way that was since replaced by a symlink can't lead outside.
+pub fn is_inside(roots: &[PathBuf], path: &Path) -> bool {
+    let real_path = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => match fs::canonicalize(parent) {
+            Ok(parent) => parent.join(name),
+            Err(_) => return false,
+        },
+        _ => return false,
+    };
+    roots
+        .iter()
+        .filter_map(|root| fs::canonicalize(root).ok())
+        .any(|root| real_path.starts_with(&root) && real_path != root)
+}
+
+#[test]
+fn is_inside_checks_real_path() {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let roots = [src.clone()];
+    assert!(is_inside(&roots, &src.join("lib.rs")));
+    assert!(is_inside(&roots, &src.join("handler")));
+    assert!(!is_inside(&roots, &src));
+    assert!(!is_inside(&roots, &src.join("handler/../../Cargo.toml")));
+    assert!(!is_inside(&roots, &src.join("..")));
+    assert!(!is_inside(&[src.join("handler")], &src.join("lib.rs")));
+}
@@ -0,0 +1,240 @@
+// copyright 2021 Remi Bernotavicius
+
+use serde_json::json;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use vdu_path_tree::{FileType, PathTree, PathTreeNode};
+
+/// The formats a `PathTree` can be exported to. Every exporter writes the tree out as it walks it,
+/// so nothing proportional to the size of the tree is buffered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// The JSON format written by `ncdu -o`.
+    Ncdu,
+    /// One `path,bytes,descendants,type,mtime` row per entry.
+    Csv,
+    /// Nested JSON objects, one per entry.
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ncdu" => Ok(Self::Ncdu),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown export format '{}', expected one of: ncdu, csv, json",
+                s
+            )),
+        }
+    }
+}
+
+fn root_name(root: &PathTreeNode) -> String {
+    root.path().to_string_lossy().into_owned()
+}
+
+fn write_ncdu_entry(name: &str, node: &PathTreeNode, output: &mut impl Write) -> io::Result<()> {
+    let metadata = node.metadata();
+    let mut info = json!({ "name": name, "asize": node.own_num_bytes() });
+    if let Some(modified) = metadata.modified {
+        info["mtime"] = modified.into();
+    }
+    if matches!(metadata.file_type, FileType::Symlink | FileType::Other) {
+        info["notreg"] = true.into();
+    }
+
+    // Directories are written as an array of their own info followed by their children.
+    let is_dir = node.is_directory();
+    if is_dir {
+        output.write_all(b"[")?;
+    }
+    serde_json::to_writer(&mut *output, &info)?;
+    for (name, child) in node.sorted_children() {
+        output.write_all(b",\n")?;
+        write_ncdu_entry(name, child, output)?;
+    }
+    if is_dir {
+        output.write_all(b"]")?;
+    }
+    Ok(())
+}
+
+/// An empty tree is written with just the header, without a root directory.
+pub fn export_ncdu(tree: &PathTree, mut output: impl Write) -> io::Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let header = json!({
+        "progname": "vdu",
+        "progver": env!("CARGO_PKG_VERSION"),
+        "timestamp": timestamp,
+    });
+
+    output.write_all(b"[1,2,")?;
+    serde_json::to_writer(&mut output, &header)?;
+    if let Some(root) = tree.root() {
+        output.write_all(b",\n")?;
+        write_ncdu_entry(&root_name(root), root, &mut output)?;
+    }
+    output.write_all(b"]\n")?;
+    output.flush()
+}
+
+pub fn export_csv(tree: &PathTree, output: impl Write) -> io::Result<()> {
+    fn write_rows(node: &PathTreeNode, writer: &mut csv::Writer<impl Write>) -> io::Result<()> {
+        let metadata = node.metadata();
+        writer.write_record(&[
+            node.path().to_string_lossy().into_owned(),
+            node.num_bytes().to_string(),
+            (node.size() - 1).to_string(),
+            metadata.file_type.as_str().into(),
+            metadata.modified.map(|m| m.to_string()).unwrap_or_default(),
+        ])?;
+        for (_, child) in node.sorted_children() {
+            write_rows(child, writer)?;
+        }
+        Ok(())
+    }
+
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(["path", "bytes", "descendants", "type", "mtime"])?;
+    if let Some(root) = tree.root() {
+        write_rows(root, &mut writer)?;
+    }
+    writer.flush()
+}
+
+fn write_json_node(name: &str, node: &PathTreeNode, output: &mut impl Write) -> io::Result<()> {
+    let metadata = node.metadata();
+    output.write_all(b"{\"name\":")?;
+    serde_json::to_writer(&mut *output, name)?;
+    write!(
+        output,
+        ",\"bytes\":{},\"descendants\":{},\"type\":\"{}\",\"mtime\":",
+        node.num_bytes(),
+        node.size() - 1,
+        metadata.file_type.as_str()
+    )?;
+    serde_json::to_writer(&mut *output, &metadata.modified)?;
+
+    if node.is_directory() {
+        output.write_all(b",\"children\":[")?;
+        for (i, (name, child)) in node.sorted_children().into_iter().enumerate() {
+            if i > 0 {
+                output.write_all(b",")?;
+            }
+            write_json_node(name, child, output)?;
+        }
+        output.write_all(b"]")?;
+    }
+    output.write_all(b"}")
+}
+
+/// An empty tree is written as `null`.
+pub fn export_json(tree: &PathTree, mut output: impl Write) -> io::Result<()> {
+    match tree.root() {
+        Some(root) => write_json_node(&root_name(root), root, &mut output)?,
+        None => output.write_all(b"null")?,
+    }
+    output.write_all(b"\n")?;
+    output.flush()
+}
+
+pub fn export_tree(format: ExportFormat, tree: &PathTree, output: impl Write) -> io::Result<()> {
+    match format {
+        ExportFormat::Ncdu => export_ncdu(tree, output),
+        ExportFormat::Csv => export_csv(tree, output),
+        ExportFormat::Json => export_json(tree, output),
+    }
+}
+
+#[cfg(test)]
+fn test_tree() -> PathTree {
+    use std::path::Path;
+    use vdu_path_tree::NodeMetadata;
+
+    let dir = NodeMetadata {
+        file_type: FileType::Directory,
+        modified: Some(100),
+    };
+    let file = NodeMetadata {
+        file_type: FileType::File,
+        modified: None,
+    };
+    let mut tree = PathTree::empty();
+    tree.add_path(Path::new("/r"), 4096, dir);
+    tree.add_path(Path::new("/r/b"), 4096, dir);
+    tree.add_path(Path::new("/r/b/c,\"d\""), 7, file);
+    tree.add_path(Path::new("/r/a"), 10, file);
+    tree
+}
+
+#[test]
+fn export_ncdu_round_trips() {
+    let mut output = vec![];
+    export_ncdu(&test_tree(), &mut output).unwrap();
+
+    let tree = crate::import_ncdu(&output[..]).unwrap();
+    assert_eq!(tree.size(), 4);
+    assert_eq!(tree.num_bytes(), 4096 + 4096 + 7 + 10);
+    let root = tree.root().unwrap();
+    assert_eq!(root.path(), std::path::Path::new("/r"));
+    assert_eq!(root.metadata().modified, Some(100));
+}
+
+#[test]
+fn export_csv_writes_rows_in_order() {
+    let mut output = vec![];
+    export_csv(&test_tree(), &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "path,bytes,descendants,type,mtime\n\
+         /r,8209,3,directory,100\n\
+         /r/a,10,0,file,\n\
+         /r/b,4103,1,directory,100\n\
+         \"/r/b/c,\"\"d\"\"\",7,0,file,\n"
+    );
+}
+
+#[test]
+fn export_json_nests_children() {
+    let mut output = vec![];
+    export_json(&test_tree(), &mut output).unwrap();
+
+    let value: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(value["name"], "/r");
+    assert_eq!(value["bytes"], 8209);
+    assert_eq!(value["children"][1]["name"], "b");
+    assert_eq!(value["children"][1]["children"][0]["name"], "c,\"d\"");
+    assert_eq!(value["children"][0]["mtime"], serde_json::Value::Null);
+    assert!(value["children"][0].get("children").is_none());
+}
+
+#[test]
+fn export_empty_tree_writes_empty_output() {
+    let export = |format| {
+        let mut output = vec![];
+        export_tree(format, &PathTree::empty(), &mut output).unwrap();
+        output
+    };
+
+    let ncdu = export(ExportFormat::Ncdu);
+    let value: serde_json::Value = serde_json::from_slice(&ncdu).unwrap();
+    assert_eq!(value.as_array().unwrap().len(), 3);
+    assert!(crate::import_ncdu(&ncdu[..]).unwrap().root().is_none());
+
+    assert_eq!(
+        String::from_utf8(export(ExportFormat::Csv)).unwrap(),
+        "path,bytes,descendants,type,mtime\n"
+    );
+    assert_eq!(
+        String::from_utf8(export(ExportFormat::Json)).unwrap(),
+        "null\n"
+    );
+}
@@ -0,0 +1,216 @@
+// copyright 2021 Remi Bernotavicius
+
+//! The JSON API the web client loads the tree through, a few levels at a time, so nothing has to
+//! wait for the whole tree to be sent.
+//!
+//! - `/api/tree/<names>?depth=<n>` sends the entry reached by following `<names>` from the root,
+//!   with `n` levels of its descendants.
+//! - `/api/search?mode=<substring|glob|regex>&pattern=<pattern>` sends what a search finds.
+//! - `/api/rescan` says whether the tree is being scanned again, and a `POST` to
+//!   `/api/rescan/<names>` starts scanning the entry again; see `VduHandler`.
+//! - `/api/delete` says whether deleting is allowed, `/api/delete/<names>` what deleting the entry
+//!   would remove, and a `POST` to it deletes the entry, or moves it to the trash. A `POST` to
+//!   `/api/restore/<id>` moves something back out of the trash.
+
+use http_io::protocol::{HttpResponse, HttpStatus};
+use std::collections::HashMap;
+use std::io;
+use vdu_path_tree::{
+    PathTree, PathTreeNode, Query, SearchMode, FORMAT_VERSION, FORMAT_VERSION_HEADER,
+};
+
+/// How many levels below the requested entry are sent when the request doesn't say.
+const DEFAULT_DEPTH: usize = 1;
+
+/// Deeper requests are cut short, so a request can't ask for the whole tree at once.
+const MAX_DEPTH: usize = 6;
+
+/// The most search matches whose paths are sent; the totals still count all of them.
+const MAX_SEARCH_MATCHES: usize = 10_000;
+
+/// Undoes the `%XX` escapes in a URI component. `None` if an escape is malformed or the result
+/// isn't UTF-8.
+pub fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn parse_query(query: &str) -> Option<HashMap<String, String>> {
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((
+                percent_decode(&key.replace('+', " "))?,
+                percent_decode(&value.replace('+', " "))?,
+            ))
+        })
+        .collect()
+}
+
+pub fn text_response(status: HttpStatus, message: String) -> HttpResponse<Box<dyn io::Read>> {
+    let mut response = HttpResponse::from_string(status, message);
+    response.add_header("Content-Type", "text/plain; charset=utf-8");
+    response
+}
+
+pub fn json_response(value: &impl serde::Serialize) -> HttpResponse<Box<dyn io::Read>> {
+    json_data_response(serde_json::to_vec(value).unwrap())
+}
+
+/// A response with `data`, which is JSON already, as its body.
+pub fn json_data_response(data: impl AsRef<[u8]> + 'static) -> HttpResponse<Box<dyn io::Read>> {
+    let length = data.as_ref().len();
+    let mut response = HttpResponse::new(
+        HttpStatus::OK,
+        Box::new(io::Cursor::new(data)) as Box<dyn io::Read>,
+    );
+    response.add_header("Content-Length", length.to_string());
+    add_json_headers(&mut response);
+    response
+}
+
+pub fn add_json_headers(response: &mut HttpResponse<Box<dyn io::Read>>) {
+    response.add_header("Content-Type", "application/json");
+    response.add_header(FORMAT_VERSION_HEADER, FORMAT_VERSION.to_string());
+}
+
+/// The names following `endpoint` in `uri`, like `/usr/share` in `/api/rescan/usr/share`, which
+/// may be none. `None` if `uri` isn't for `endpoint`.
+pub fn endpoint_names<'a>(uri: &'a str, endpoint: &str) -> Option<&'a str> {
+    uri.strip_prefix(endpoint)
+        .filter(|names| names.is_empty() || names.starts_with('/'))
+}
+
+/// Finds the entry reached by following the `/`-separated, percent-encoded `names` from the
+/// root, along with the name the API gives it. Otherwise gives the status and message to respond
+/// with.
+pub fn find_entry<'a>(
+    tree: &'a PathTree,
+    names: &str,
+) -> Result<(&'a PathTreeNode, String), (HttpStatus, String)> {
+    let names = names
+        .split('/')
+        .filter(|n| !n.is_empty())
+        .map(percent_decode)
+        .collect::<Option<Vec<_>>>()
+        .ok_or((HttpStatus::BadRequest, "bad path".into()))?;
+
+    let root = tree
+        .root()
+        .ok_or((HttpStatus::NotFound, "the tree is empty".into()))?;
+    let path = names.iter().fold(root.path().to_owned(), |p, n| p.join(n));
+    let node = tree.get(&path).ok_or_else(|| {
+        (
+            HttpStatus::NotFound,
+            format!("{} isn't in the tree", path.display()),
+        )
+    })?;
+    let name = match names.last() {
+        Some(name) => name.clone(),
+        None => root.path().to_string_lossy().into_owned(),
+    };
+    Ok((node, name))
+}
+
+fn get_subtree(
+    tree: &PathTree,
+    names: &str,
+    query: &HashMap<String, String>,
+) -> Result<Vec<u8>, (HttpStatus, String)> {
+    let depth = match query.get("depth").map(|d| d.parse::<usize>()) {
+        None => DEFAULT_DEPTH,
+        Some(Ok(depth)) => depth.min(MAX_DEPTH),
+        Some(Err(e)) => return Err((HttpStatus::BadRequest, format!("bad depth: {}", e))),
+    };
+    let (node, name) = find_entry(tree, names)?;
+    Ok(serde_json::to_vec(&node.subtree(&name, depth)).unwrap())
+}
+
+fn get_search(
+    tree: &PathTree,
+    query: &HashMap<String, String>,
+) -> Result<Vec<u8>, (HttpStatus, String)> {
+    let mode = match query.get("mode").map(|m| m.parse::<SearchMode>()) {
+        None => SearchMode::Substring,
+        Some(Ok(mode)) => mode,
+        Some(Err(e)) => return Err((HttpStatus::BadRequest, e)),
+    };
+    let pattern = query.get("pattern").map(|p| &p[..]).unwrap_or("");
+    match Query::new(mode, pattern) {
+        Ok(search) => {
+            Ok(serde_json::to_vec(&tree.search_response(&search, MAX_SEARCH_MATCHES)).unwrap())
+        }
+        Err(e) => Err((HttpStatus::BadRequest, format!("invalid pattern: {}", e))),
+    }
+}
+
+/// Answers `uri` if it is part of the API, with the JSON to respond with. Otherwise gives the
+/// status and message to respond with.
+pub fn get(tree: &PathTree, uri: &str) -> Option<Result<Vec<u8>, (HttpStatus, String)>> {
+    let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+    let query = match parse_query(query) {
+        Some(query) => query,
+        None => return Some(Err((HttpStatus::BadRequest, "bad query".into()))),
+    };
+
+    if path == "/api/search" {
+        Some(get_search(tree, &query))
+    } else if path == "/api/tree" {
+        Some(get_subtree(tree, "", &query))
+    } else {
+        let names = path.strip_prefix("/api/tree/")?;
+        Some(get_subtree(tree, names, &query))
+    }
+}
+
+#[cfg(test)]
+fn get_json(tree: &PathTree, uri: &str) -> serde_json::Value {
+    serde_json::from_slice(&get(tree, uri).unwrap().unwrap()).unwrap()
+}
+
+#[test]
+fn query_is_decoded() {
+    let query = parse_query("pattern=a%2Bb+c&mode=glob&flag").unwrap();
+    assert_eq!(query["pattern"], "a+b c");
+    assert_eq!(query["mode"], "glob");
+    assert_eq!(query["flag"], "");
+    assert!(parse_query("pattern=%4").is_none());
+    assert_eq!(percent_decode("caf%C3%A9"), Some("café".into()));
+}
+
+#[test]
+fn subtree_follows_names_from_root() {
+    use std::path::Path;
+    use vdu_path_tree::NodeMetadata;
+
+    let mut tree = PathTree::empty();
+    for &(path, num_bytes) in &[("/r", 0), ("/r/a b", 0), ("/r/a b/c", 3), ("/r/a b/c/d", 4)] {
+        tree.add_path(Path::new(path), num_bytes, NodeMetadata::unknown());
+    }
+
+    let value = get_json(&tree, "/api/tree?depth=0");
+    assert_eq!(value["name"], "/r");
+    assert_eq!(value["num_bytes"], 7);
+    assert!(value.get("children").is_none());
+
+    let value = get_json(&tree, "/api/tree/a%20b?depth=1");
+    assert_eq!(value["name"], "a b");
+    assert_eq!(value["children"][0]["name"], "c");
+    assert_eq!(value["children"][0]["num_descendants"], 1);
+    assert!(value["children"][0].get("children").is_none());
+
+    let (status, _) = get(&tree, "/api/tree/nope").unwrap().unwrap_err();
+    assert_eq!(status, HttpStatus::NotFound);
+    assert!(get(&tree, "/index.html").is_none());
+}
@@ -0,0 +1,154 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Keeping the tree from anyone who can connect to the server but wasn't given its link. The link
+//! carries an access token, which the first response swaps for a cookie so it doesn't stay in the
+//! address bar, and every request after that has to have the cookie.
+
+use super::api::{percent_decode, text_response};
+use super::cache::request_header;
+use http_io::protocol::{HttpHeaders, HttpResponse, HttpStatus};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash as _, Hasher as _};
+use std::io;
+
+/// Makes an access token that can't be guessed.
+pub fn random_token() -> io::Result<String> {
+    let mut bytes = [0; 16];
+    getrandom::fill(&mut bytes).map_err(io::Error::other)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Compares without stopping at the first difference, so how long it takes doesn't give away how
+/// much of a guess was right.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |d, (a, b)| d | (a ^ b)) == 0
+}
+
+pub struct AccessToken {
+    token: String,
+    /// Cookies aren't kept apart by port, so servers with different tokens use different cookies.
+    cookie_name: String,
+}
+
+impl AccessToken {
+    pub fn new(token: String) -> Self {
+        let mut hasher = DefaultHasher::new();
+        token.hash(&mut hasher);
+        Self {
+            cookie_name: format!("vdu_token_{:016x}", hasher.finish()),
+            token,
+        }
+    }
+
+    fn has_cookie(&self, headers: &HttpHeaders) -> bool {
+        let cookies = match request_header(headers, "Cookie") {
+            Some(cookies) => cookies,
+            None => return false,
+        };
+        cookies.split(';').any(|cookie| {
+            matches!(cookie.trim().split_once('='),
+                Some((name, value)) if name == self.cookie_name && constant_time_eq(value, &self.token))
+        })
+    }
+
+    /// Sends the browser back to `path` and the rest of the query without the token, setting the
+    /// cookie instead.
+    fn redirect_with_cookie(&self, path: &str, rest: &[&str]) -> HttpResponse<Box<dyn io::Read>> {
+        let location = match rest {
+            [] => path.to_owned(),
+            rest => format!("{}?{}", path, rest.join("&")),
+        };
+        let mut response = HttpResponse::new(HttpStatus::SeeOther, Box::new(io::empty()) as _);
+        response.add_header("Location", location);
+        response.add_header(
+            "Set-Cookie",
+            format!(
+                "{}={}; Path=/; HttpOnly; SameSite=Lax",
+                self.cookie_name, self.token
+            ),
+        );
+        response.add_header("Cache-Control", "no-store");
+        response
+    }
+
+    /// What to respond with instead of answering a request for `uri`, unless the request has the
+    /// token, in its query or a cookie.
+    pub fn check(
+        &self,
+        uri: &str,
+        headers: &HttpHeaders,
+    ) -> Option<HttpResponse<Box<dyn io::Read>>> {
+        let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+        let (tokens, rest): (Vec<_>, Vec<_>) = query
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .partition(|p| p.starts_with("token="));
+        let in_query = tokens.iter().any(|t| {
+            percent_decode(&t["token=".len()..]).is_some_and(|t| constant_time_eq(&t, &self.token))
+        });
+
+        if in_query {
+            Some(self.redirect_with_cookie(path, &rest))
+        } else if self.has_cookie(headers) {
+            None
+        } else {
+            Some(text_response(
+                HttpStatus::Forbidden,
+                "open the link with the access token that vdu printed when it started".into(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+fn headers(cookie: Option<&str>) -> HttpHeaders {
+    use http_io::protocol::{HttpMethod, HttpRequest};
+
+    let mut request = HttpRequest::new(HttpMethod::Get, "/");
+    if let Some(cookie) = cookie {
+        request.add_header("Cookie", cookie);
+    }
+    request.headers
+}
+
+#[test]
+fn token_in_link_is_swapped_for_cookie() {
+    let access = AccessToken::new("s3cret".into());
+
+    let response = access.check("/?a=1&token=s3cret", &headers(None)).unwrap();
+    assert_eq!(response.status, HttpStatus::SeeOther);
+    assert_eq!(response.get_header("Location"), Some("/?a=1"));
+    let cookie = response.get_header("Set-Cookie").unwrap();
+    assert!(cookie.starts_with(&format!("{}=s3cret;", access.cookie_name)));
+    assert!(cookie.contains("HttpOnly"));
+    assert!(cookie.contains("SameSite=Lax"));
+
+    let cookie = format!("other=1; {}=s3cret", access.cookie_name);
+    assert!(access.check("/api/tree", &headers(Some(&cookie))).is_none());
+}
+
+#[test]
+fn requests_without_token_are_refused() {
+    let access = AccessToken::new("s3cret".into());
+    let forbidden = |uri: &str, cookie: Option<&str>| {
+        access.check(uri, &headers(cookie)).map(|r| r.status) == Some(HttpStatus::Forbidden)
+    };
+    assert!(forbidden("/", None));
+    assert!(forbidden("/?token=guess", None));
+    assert!(forbidden("/", Some("vdu_token=s3cret")));
+    assert!(forbidden(
+        "/",
+        Some(&format!("{}=s3cre", access.cookie_name))
+    ));
+    assert_ne!(
+        access.cookie_name,
+        AccessToken::new("other".into()).cookie_name
+    );
+}
+
+#[test]
+fn random_tokens_differ() {
+    let token = random_token().unwrap();
+    assert_eq!(token.len(), 32);
+    assert_ne!(token, random_token().unwrap());
+}
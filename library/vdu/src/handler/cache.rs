@@ -0,0 +1,92 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Conditional requests, so a browser that already has a response can be told it is still good
+//! instead of being sent it again.
+
+use http_io::protocol::{HttpHeaders, HttpResponse, HttpStatus};
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Looks up a request header. `HttpHeaders` compares names exactly, but clients don't agree on
+/// their case, so the usual capitalization and all lowercase are both tried.
+pub fn request_header<'a>(headers: &'a HttpHeaders, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .or_else(|| headers.get(&name.to_lowercase()))
+}
+
+/// What identifies a version of a response, sent as `ETag` and `Last-Modified`.
+pub struct Validators {
+    etag: String,
+    last_modified: String,
+}
+
+impl Validators {
+    /// Validators for something last changed at `modified`. Compressed responses get their own
+    /// entity tags, since they aren't the same bytes as the uncompressed ones.
+    pub fn new(modified: SystemTime, gzipped: bool) -> Self {
+        let nanos = modified
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let suffix = if gzipped { "-gzip" } else { "" };
+        Self {
+            etag: format!("\"{:x}{}\"", nanos, suffix),
+            last_modified: httpdate::fmt_http_date(modified),
+        }
+    }
+
+    /// Whether the client sending `headers` already has this version. `If-None-Match` takes
+    /// precedence; `If-Modified-Since` is only compared against the exact date that was sent, since
+    /// that is all a browser sends back.
+    pub fn is_fresh(&self, headers: &HttpHeaders) -> bool {
+        if let Some(tags) = request_header(headers, "If-None-Match") {
+            return tags
+                .split(',')
+                .map(|t| t.trim().trim_start_matches("W/"))
+                .any(|t| t == "*" || t == self.etag);
+        }
+        request_header(headers, "If-Modified-Since") == Some(&self.last_modified[..])
+    }
+
+    pub fn add_to(&self, response: &mut HttpResponse<Box<dyn io::Read>>) {
+        response.add_header("ETag", self.etag.clone());
+        response.add_header("Last-Modified", self.last_modified.clone());
+        // the browser may keep the response, but has to check it is still current before using it
+        response.add_header("Cache-Control", "no-cache");
+    }
+
+    pub fn not_modified(&self) -> HttpResponse<Box<dyn io::Read>> {
+        let mut response = HttpResponse::new(HttpStatus::NotModified, Box::new(io::empty()) as _);
+        self.add_to(&mut response);
+        response
+    }
+}
+
+#[test]
+fn validators_match_conditional_headers() {
+    use http_io::protocol::{HttpMethod, HttpRequest};
+
+    let validators = Validators::new(SystemTime::now(), false);
+    let request = |name: &str, value: &str| {
+        let mut request = HttpRequest::new(HttpMethod::Get, "/api/tree");
+        request.add_header(name, value);
+        request.headers
+    };
+
+    let etag = validators.etag.clone();
+    assert!(validators.is_fresh(&request("If-None-Match", &etag)));
+    assert!(validators.is_fresh(&request("if-none-match", &format!("\"x\", W/{}", etag))));
+    assert!(!validators.is_fresh(&request("If-None-Match", "\"x\"")));
+
+    let date = validators.last_modified.clone();
+    assert!(validators.is_fresh(&request("If-Modified-Since", &date)));
+    assert!(!validators.is_fresh(&request("Accept", "*/*")));
+
+    let gzipped = Validators::new(UNIX_EPOCH, true);
+    assert_ne!(gzipped.etag, Validators::new(UNIX_EPOCH, false).etag);
+
+    let time = UNIX_EPOCH + std::time::Duration::from_secs(784_111_777);
+    let validators = Validators::new(time, false);
+    assert_eq!(validators.last_modified, "Sun, 06 Nov 1994 08:49:37 GMT");
+}
@@ -0,0 +1,75 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Compressing responses for clients that accept gzip, which helps a lot when the server is
+//! reached through something slow like an SSH tunnel.
+
+use super::cache::request_header;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use http_io::protocol::{HttpHeaders, HttpResponse, HttpStatus};
+use std::io::{self, Write};
+use vdu_path_tree::DECODED_LENGTH_HEADER;
+
+/// Whether the `Accept-Encoding` header in `headers` allows gzip.
+pub fn accepts_gzip(headers: &HttpHeaders) -> bool {
+    let accepted = match request_header(headers, "Accept-Encoding") {
+        Some(accepted) => accepted,
+        None => return false,
+    };
+    accepted.split(',').any(|coding| {
+        let mut params = coding.split(';').map(str::trim);
+        let name = params.next().unwrap_or("");
+        // "gzip;q=0" means anything but gzip
+        let refused = params.any(|p| {
+            p.strip_prefix("q=")
+                .and_then(|q| q.parse::<f64>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+        (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+    })
+}
+
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Marks `response` as one that depends on whether the client accepts gzip, so caches keep the
+/// compressed and uncompressed versions apart.
+pub fn add_vary(response: &mut HttpResponse<Box<dyn io::Read>>) {
+    response.add_header("Vary", "Accept-Encoding");
+}
+
+/// A response with `compressed` as its body, in place of a body of `decoded_length` bytes.
+pub fn gzip_response(
+    status: HttpStatus,
+    compressed: impl AsRef<[u8]> + 'static,
+    decoded_length: usize,
+) -> HttpResponse<Box<dyn io::Read>> {
+    let length = compressed.as_ref().len();
+    let mut response = HttpResponse::new(status, Box::new(io::Cursor::new(compressed)) as _);
+    response.add_header("Content-Encoding", "gzip");
+    response.add_header("Content-Length", length.to_string());
+    // the browser only shows the client the decoded body, so it needs this to show progress
+    response.add_header(DECODED_LENGTH_HEADER, decoded_length.to_string());
+    response
+}
+
+#[cfg(test)]
+fn headers(accept_encoding: &str) -> HttpHeaders {
+    use http_io::protocol::{HttpMethod, HttpRequest};
+
+    let mut request = HttpRequest::new(HttpMethod::Get, "/");
+    request.add_header("Accept-Encoding", accept_encoding);
+    request.headers
+}
+
+#[test]
+fn accept_encoding_is_parsed() {
+    assert!(accepts_gzip(&headers("gzip, deflate, br")));
+    assert!(accepts_gzip(&headers("br;q=1.0, GZIP;q=0.5")));
+    assert!(accepts_gzip(&headers("*")));
+    assert!(!accepts_gzip(&headers("gzip;q=0, br")));
+    assert!(!accepts_gzip(&headers("identity")));
+}
@@ -1,7 +1,31 @@
 // copyright 2021 Remi Bernotavicius
 
+use http_io::protocol::HttpRequest;
+use std::io;
+
+pub use self::auth::random_token;
 pub use self::tar::TarHandler;
 pub use self::vdu::VduHandler;
 
+mod api;
+mod auth;
+mod cache;
+mod encoding;
+mod rescan;
 mod tar;
 mod vdu;
+
+/// Reads a request from `stream` and writes `handler`'s response to it.
+pub fn serve_one<S: io::Read + io::Write>(
+    handler: &mut VduHandler,
+    stream: &mut S,
+) -> io::Result<()> {
+    let mut response = match HttpRequest::deserialize(io::BufReader::new(&mut *stream)) {
+        Ok(request) => handler.handle(request),
+        Err(error) => error.into(),
+    };
+
+    response.serialize(&mut *stream)?;
+    io::copy(&mut response.body, stream)?;
+    Ok(())
+}
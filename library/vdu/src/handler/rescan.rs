@@ -0,0 +1,134 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Scanning the tree, or part of it, again while the server keeps answering requests with the old
+//! one.
+
+use crate::walk::scan_in_background;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use vdu_path_tree::PathTree;
+
+/// A scan running on another thread.
+pub struct Rescan {
+    /// What is being scanned: the root of the tree, or an entry in it.
+    path: PathBuf,
+    /// Whether the new tree replaces the whole tree rather than just the entry at `path`.
+    whole_tree: bool,
+    entries: Arc<AtomicU64>,
+    result: mpsc::Receiver<io::Result<PathTree>>,
+}
+
+impl Rescan {
+    fn start(path: &Path, paths: Vec<PathBuf>, whole_tree: bool) -> Self {
+        let entries = Arc::new(AtomicU64::new(0));
+        let (sender, result) = mpsc::channel();
+
+        let entries_for_scan = entries.clone();
+        thread::spawn(move || {
+            let scan = || {
+                let mut tree = PathTree::empty();
+                for path in &paths {
+                    tree.merge(scan_in_background(path, &entries_for_scan)?);
+                }
+                Ok(tree)
+            };
+            // nothing is listening any more if the server stopped
+            let _ = sender.send(scan());
+        });
+
+        Self {
+            path: path.to_owned(),
+            whole_tree,
+            entries,
+            result,
+        }
+    }
+
+    /// Scans each of `roots` and merges them, the way the tree at `path` was built to begin with.
+    pub fn whole_tree(path: &Path, roots: &[PathBuf]) -> Self {
+        Self::start(path, roots.to_vec(), true)
+    }
+
+    /// Scans just the entry at `path`.
+    pub fn subtree(path: &Path) -> Self {
+        Self::start(path, vec![path.to_owned()], false)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> u64 {
+        self.entries.load(Ordering::Relaxed)
+    }
+
+    /// Puts what was found in `tree` once the scan is done, or gives the error it stopped at.
+    /// `None` while it is still running.
+    pub fn finish(&self, tree: &mut PathTree) -> Option<io::Result<()>> {
+        let result = match self.result.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Err(io::Error::other("the scan stopped unexpectedly"))
+            }
+        };
+        match result {
+            Ok(new_tree) if self.whole_tree => *tree = new_tree,
+            Ok(new_tree) => tree.load_subtree(new_tree),
+            // it was deleted since it was last scanned
+            Err(e) if !self.whole_tree && e.kind() == io::ErrorKind::NotFound => {
+                tree.remove_path(&self.path);
+            }
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Ok(()))
+    }
+}
+
+#[cfg(test)]
+fn wait_for(rescan: &Rescan, tree: &mut PathTree) -> io::Result<()> {
+    loop {
+        if let Some(result) = rescan.finish(tree) {
+            return result;
+        }
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn rescan_replaces_whole_tree() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut tree = PathTree::empty();
+    let rescan = Rescan::whole_tree(&root, std::slice::from_ref(&root));
+    wait_for(&rescan, &mut tree).unwrap();
+    assert_eq!(tree.root().unwrap().path(), root);
+    assert!(tree.get(&root.join("handler/rescan.rs")).is_some());
+    assert_eq!(rescan.entries(), tree.size() as u64);
+}
+
+#[test]
+fn rescan_updates_subtree() {
+    use vdu_path_tree::NodeMetadata;
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let handler = root.join("handler");
+    let mut tree = PathTree::empty();
+    tree.add_path(&root, 0, NodeMetadata::unknown());
+    tree.add_path(&root.join("lib.rs"), 10, NodeMetadata::unknown());
+    tree.add_path(&handler, 0, NodeMetadata::unknown());
+    tree.add_path(&handler.join("gone.rs"), 10, NodeMetadata::unknown());
+
+    wait_for(&Rescan::subtree(&handler), &mut tree).unwrap();
+    assert!(tree.get(&handler.join("gone.rs")).is_none());
+    assert!(tree.get(&handler.join("rescan.rs")).is_some());
+    assert_eq!(tree.get(&root.join("lib.rs")).unwrap().num_bytes(), 10);
+
+    // a deleted entry is taken out of the tree
+    let gone = root.join("gone");
+    tree.add_path(&gone, 10, NodeMetadata::unknown());
+    wait_for(&Rescan::subtree(&gone), &mut tree).unwrap();
+    assert!(tree.get(&gone).is_none());
+}
@@ -1,9 +1,9 @@
-use http_io::error::{Error, Result};
-use http_io::protocol::{HttpBody, HttpResponse, HttpStatus};
-use http_io::server::HttpRequestHandler;
+use super::encoding::{add_vary, gzip, gzip_response};
+use http_io::protocol::{HttpResponse, HttpStatus};
 use std::collections::HashMap;
 use std::io;
 use std::path::Path;
+use std::rc::Rc;
 
 fn mime_for_path(path: &str) -> &'static str {
     if let Some(ext) = Path::new(path).extension() {
@@ -19,8 +19,16 @@ fn mime_for_path(path: &str) -> &'static str {
     "application/octet-stream"
 }
 
+/// A file from the archive.
+struct Asset {
+    data: &'static [u8],
+    /// Compressed when the archive is loaded, so it isn't done again for every request. `None`
+    /// when compressing doesn't make it smaller.
+    gzipped: Option<Rc<[u8]>>,
+}
+
 pub struct TarHandler {
-    map: HashMap<String, &'static [u8]>,
+    map: HashMap<String, Asset>,
 }
 
 impl TarHandler {
@@ -34,47 +42,40 @@ impl TarHandler {
             let path = header.path().unwrap().to_str().unwrap().into();
             let start = entry.raw_file_position() as usize;
             let end = start + header.size().unwrap() as usize;
-            map.insert(path, &bytes[start..end]);
+            let data = &bytes[start..end];
+            let gzipped = Some(gzip(data))
+                .filter(|g| g.len() < data.len())
+                .map(Rc::from);
+            map.insert(path, Asset { data, gzipped });
         }
         Self { map }
     }
 
-    fn get_file(&self, path: &str) -> Option<HttpResponse<Box<dyn io::Read>>> {
+    fn get_file(&self, path: &str, accepts_gzip: bool) -> Option<HttpResponse<Box<dyn io::Read>>> {
         let mut path = format!(".{}", path);
 
         if path == "./" {
             path = "./index.html".into();
         }
 
-        self.map.get(&path[..]).map(|&b| {
-            let mut response = HttpResponse::new(HttpStatus::OK, Box::new(b) as Box<dyn io::Read>);
+        self.map.get(&path[..]).map(|asset| {
+            let mut response = match &asset.gzipped {
+                Some(gzipped) if accepts_gzip => {
+                    gzip_response(HttpStatus::OK, gzipped.clone(), asset.data.len())
+                }
+                _ => HttpResponse::new(HttpStatus::OK, Box::new(asset.data) as _),
+            };
             response.add_header("Content-Type", mime_for_path(&path));
+            add_vary(&mut response);
             response
         })
     }
-}
 
-impl<I: io::Read> HttpRequestHandler<I> for TarHandler {
-    type Error = Error;
-    fn get(&mut self, uri: String) -> Result<HttpResponse<Box<dyn io::Read>>> {
-        if let Some(resp) = self.get_file(&uri[..]) {
-            Ok(resp)
-        } else {
-            Ok(HttpResponse::new(
-                HttpStatus::NotFound,
-                Box::new(io::empty()),
-            ))
+    /// Responds with the file at `uri`, compressed if the client accepts gzip.
+    pub fn get(&self, uri: &str, accepts_gzip: bool) -> HttpResponse<Box<dyn io::Read>> {
+        match self.get_file(uri, accepts_gzip) {
+            Some(response) => response,
+            None => HttpResponse::new(HttpStatus::NotFound, Box::new(io::empty())),
         }
     }
-
-    fn put(
-        &mut self,
-        _uri: String,
-        _stream: HttpBody<&mut I>,
-    ) -> Result<HttpResponse<Box<dyn io::Read>>> {
-        Ok(HttpResponse::new(
-            HttpStatus::NotFound,
-            Box::new(io::empty()),
-        ))
-    }
 }
@@ -1,52 +1,687 @@
 // copyright 2021 Remi Bernotavicius
 
-use super::TarHandler;
-use http_io::error::{Error, Result};
-use http_io::protocol::{HttpBody, HttpResponse, HttpStatus};
-use http_io::server::HttpRequestHandler;
+use super::auth::AccessToken;
+use super::cache::{request_header, Validators};
+use super::encoding::{accepts_gzip, add_vary, gzip, gzip_response};
+use super::rescan::Rescan;
+use super::{api, TarHandler};
+use crate::delete::{delete_path, is_inside, trash_path, DeleteMode, TrashedPath};
+use crate::ServerOptions;
+use http_io::error::Result;
+use http_io::protocol::{HttpHeaders, HttpMethod, HttpRequest, HttpResponse, HttpStatus};
+use std::collections::hash_map::{Entry, HashMap};
 use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::SystemTime;
 
-use vdu_path_tree::PathTree;
+use vdu_path_tree::{
+    DeleteStatus, PathTree, PathTreeNode, RescanStatus, TrashedEntry, API_REQUEST_HEADER,
+    CONFIRM_DELETE_HEADER,
+};
 
-const WEB_TAR: &'static [u8] = include_bytes!("../../web.tar");
+const WEB_TAR: &[u8] = include_bytes!("../../web.tar");
+
+/// How many of the largest entries a deletion would remove are listed before asking to go ahead.
+const MAX_PREVIEW_ENTRIES: usize = 10;
+
+/// How many API responses are kept before starting over, since every search is a different one.
+const MAX_CACHED_RESPONSES: usize = 100;
+
+/// The body of an API response.
+struct CachedBody {
+    data: Rc<[u8]>,
+    /// Made the first time a client accepting gzip asks for it.
+    gzipped: Option<Rc<[u8]>>,
+}
+
+/// The bodies of API responses, by the URI they answered, so the tree isn't looked through again
+/// when the web client asks for the same part of it.
+struct ApiCache {
+    /// The `tree_modified` they were made at.
+    modified: SystemTime,
+    bodies: HashMap<String, CachedBody>,
+}
+
+impl ApiCache {
+    fn new(modified: SystemTime) -> Self {
+        Self {
+            modified,
+            bodies: HashMap::new(),
+        }
+    }
+}
 
 pub struct VduHandler {
     tar: TarHandler,
     tree: PathTree,
+    /// When `tree` last changed. Responses made from the tree are identified by it, so browsers
+    /// can keep them until it changes again.
+    tree_modified: SystemTime,
+    api_cache: ApiCache,
+    /// The paths the tree was scanned from, empty when it was imported.
+    scan_roots: Vec<PathBuf>,
+    rescan: Option<Rescan>,
+    /// Why the last scan failed, until another one is started.
+    rescan_error: Option<String>,
+    allow_delete: bool,
+    delete_mode: DeleteMode,
+    /// What has been moved to the trash, most recent last, each with the ID it is restored by.
+    trashed: Vec<(u64, TrashedPath)>,
+    next_trashed_id: u64,
+    /// Used instead of the trash in the home directory, so tests don't touch the real one.
+    home_trash: Option<PathBuf>,
+    /// What every request has to have, unless the server was started without one.
+    access_token: Option<AccessToken>,
 }
 
 impl VduHandler {
-    pub fn new(tree: PathTree) -> Self {
+    pub fn new(tree: PathTree, options: ServerOptions) -> Self {
+        let tree_modified = SystemTime::now();
         Self {
             tar: TarHandler::from_memory(WEB_TAR),
             tree,
+            tree_modified,
+            api_cache: ApiCache::new(tree_modified),
+            scan_roots: options.scan_roots,
+            rescan: None,
+            rescan_error: None,
+            allow_delete: options.allow_delete,
+            delete_mode: options.delete_mode,
+            trashed: vec![],
+            next_trashed_id: 0,
+            home_trash: None,
+            access_token: options.token.map(AccessToken::new),
         }
     }
 
-    fn get_tree(&self) -> HttpResponse<Box<dyn io::Read>> {
-        let data = bincode::serialize(&self.tree).unwrap();
-        let body: Box<dyn io::Read> = Box::new(io::Cursor::new(data));
-        let mut response = HttpResponse::new(HttpStatus::OK, body);
-        response.add_header("Content-Type", "application/octet-stream");
+    /// Swaps in what a scan found if it is done. The server only does anything when it gets a
+    /// request, so this is checked then; the web client asks how the scan is going until it is.
+    fn finish_rescan(&mut self) {
+        let result = match &self.rescan {
+            Some(rescan) => match rescan.finish(&mut self.tree) {
+                Some(result) => result,
+                None => return,
+            },
+            None => return,
+        };
+        match result {
+            Ok(()) => {
+                log::info!("found {} files", self.tree.size());
+                self.tree_modified = SystemTime::now();
+            }
+            Err(e) => {
+                log::warn!("scan failed: {}", e);
+                self.rescan_error = Some(e.to_string());
+            }
+        }
+        self.rescan = None;
+    }
+
+    fn rescan_status(&self) -> HttpResponse<Box<dyn io::Read>> {
+        api::json_response(&RescanStatus {
+            can_rescan: !self.scan_roots.is_empty(),
+            scanning: self.rescan.as_ref().map(|r| r.path().display().to_string()),
+            entries: self.rescan.as_ref().map_or(0, Rescan::entries),
+            error: self.rescan_error.clone(),
+        })
+    }
+
+    /// Starts scanning the entry at `names` again, or the whole tree if it isn't inside any of
+    /// the paths the tree was scanned from (like the directory the paths were combined under).
+    fn start_rescan(&mut self, names: &str) -> HttpResponse<Box<dyn io::Read>> {
+        if self.scan_roots.is_empty() {
+            return api::text_response(
+                HttpStatus::Forbidden,
+                "the tree was imported, so it can't be scanned again".into(),
+            );
+        }
+        if self.rescan.is_some() {
+            return api::text_response(HttpStatus::Conflict, "a scan is already running".into());
+        }
+
+        let path = match api::find_entry(&self.tree, names) {
+            Ok((node, _)) => node.path().to_owned(),
+            Err((status, message)) => return api::text_response(status, message),
+        };
+        let rescan = if self.scan_roots.iter().any(|r| path.starts_with(r)) {
+            Rescan::subtree(&path)
+        } else {
+            Rescan::whole_tree(&path, &self.scan_roots)
+        };
+        self.rescan = Some(rescan);
+        self.rescan_error = None;
+
+        let mut response = self.rescan_status();
+        response.status = HttpStatus::Accepted;
         response
     }
-}
 
-impl<I: io::Read> HttpRequestHandler<I> for VduHandler {
-    type Error = Error;
-    fn get(&mut self, uri: String) -> Result<HttpResponse<Box<dyn io::Read>>> {
-        if uri == "/tree" {
-            Ok(self.get_tree())
+    /// The entry at `names`, if the web client may delete it: deleting has to be turned on, and
+    /// only what is inside the scanned paths can be deleted.
+    fn deletable(&self, names: &str) -> std::result::Result<&PathTreeNode, (HttpStatus, String)> {
+        if !self.allow_delete {
+            return Err((
+                HttpStatus::Forbidden,
+                "deleting is turned off; start vdu with --allow-delete to turn it on".into(),
+            ));
+        }
+        let (node, _) = api::find_entry(&self.tree, names)?;
+        if !is_inside(&self.scan_roots, node.path()) {
+            return Err((
+                HttpStatus::Forbidden,
+                format!("{} isn't inside the scanned paths", node.path().display()),
+            ));
+        }
+        Ok(node)
+    }
+
+    fn delete_status(&self) -> HttpResponse<Box<dyn io::Read>> {
+        api::json_response(&DeleteStatus {
+            allowed: self.allow_delete,
+            trash: self.delete_mode == DeleteMode::Trash,
+            trashed: self
+                .trashed
+                .iter()
+                .map(|(id, t)| TrashedEntry {
+                    id: *id,
+                    path: t.path().display().to_string(),
+                    num_bytes: t.num_bytes(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Says whether deleting is allowed, or with `names`, what deleting that entry would remove.
+    fn get_delete(&self, names: &str) -> HttpResponse<Box<dyn io::Read>> {
+        if names.is_empty() {
+            return self.delete_status();
+        }
+        match self.deletable(names) {
+            Ok(node) => api::json_response(
+                &node.delete_preview(MAX_PREVIEW_ENTRIES, self.delete_mode == DeleteMode::Trash),
+            ),
+            Err((status, message)) => api::text_response(status, message),
+        }
+    }
+
+    /// Deletes the entry at `names`, if the request confirms it is the one meant.
+    fn delete(&mut self, names: &str, headers: &HttpHeaders) -> HttpResponse<Box<dyn io::Read>> {
+        if self.rescan.is_some() {
+            return api::text_response(
+                HttpStatus::Conflict,
+                "wait for the scan to finish before deleting anything".into(),
+            );
+        }
+        let preview = match self.deletable(names) {
+            Ok(node) => {
+                node.delete_preview(MAX_PREVIEW_ENTRIES, self.delete_mode == DeleteMode::Trash)
+            }
+            Err((status, message)) => return api::text_response(status, message),
+        };
+        let confirmed =
+            request_header(headers, CONFIRM_DELETE_HEADER).and_then(api::percent_decode);
+        if confirmed.as_ref() != Some(&preview.path) {
+            return api::text_response(
+                HttpStatus::BadRequest,
+                format!("deleting {} wasn't confirmed", preview.path),
+            );
+        }
+
+        let path = preview.path.as_ref();
+        let deleted = match self.delete_mode {
+            DeleteMode::Trash => {
+                trash_path(&mut self.tree, path, self.home_trash.as_deref()).map(|trashed| {
+                    self.trashed.push((self.next_trashed_id, trashed));
+                    self.next_trashed_id += 1;
+                })
+            }
+            DeleteMode::Permanent => delete_path(&mut self.tree, path).map_err(|e| {
+                io::Error::new(e.kind(), format!("{}; rescan to see what is left", e))
+            }),
+        };
+        match deleted {
+            Ok(()) => {
+                self.tree_modified = SystemTime::now();
+                api::json_response(&preview)
+            }
+            Err(e) => api::text_response(
+                HttpStatus::InternalServerError,
+                format!("couldn't delete {}: {}", preview.path, e),
+            ),
+        }
+    }
+
+    /// Moves what was moved to the trash with the given ID back to where it was.
+    fn restore(&mut self, id: &str) -> HttpResponse<Box<dyn io::Read>> {
+        if self.rescan.is_some() {
+            return api::text_response(
+                HttpStatus::Conflict,
+                "wait for the scan to finish before restoring anything".into(),
+            );
+        }
+        let index = id
+            .parse::<u64>()
+            .ok()
+            .and_then(|id| self.trashed.iter().position(|(i, _)| *i == id));
+        let index = match index {
+            Some(index) => index,
+            None => {
+                return api::text_response(
+                    HttpStatus::NotFound,
+                    format!("nothing in the trash has ID {}", id),
+                )
+            }
+        };
+
+        let trashed = &mut self.trashed[index].1;
+        match trashed.restore(&mut self.tree) {
+            Ok(()) => {
+                self.trashed.remove(index);
+                self.tree_modified = SystemTime::now();
+                self.delete_status()
+            }
+            Err(e) => api::text_response(
+                HttpStatus::Conflict,
+                format!("couldn't restore {}: {}", trashed.path().display(), e),
+            ),
+        }
+    }
+
+    fn post(&mut self, uri: &str, headers: &HttpHeaders) -> HttpResponse<Box<dyn io::Read>> {
+        if request_header(headers, API_REQUEST_HEADER).is_none() {
+            return api::text_response(
+                HttpStatus::Forbidden,
+                format!(
+                    "requests that change anything need the {} header",
+                    API_REQUEST_HEADER
+                ),
+            );
+        }
+        if let Some(names) = api::endpoint_names(uri, "/api/rescan") {
+            self.start_rescan(names)
+        } else if let Some(names) = api::endpoint_names(uri, "/api/delete") {
+            self.delete(names, headers)
+        } else if let Some(id) = uri.strip_prefix("/api/restore/") {
+            self.restore(id)
         } else {
-            <TarHandler as HttpRequestHandler<I>>::get(&mut self.tar, uri)
+            HttpResponse::from_string(HttpStatus::MethodNotAllowed, "method not allowed")
         }
     }
 
-    fn put(
+    /// Answers `uri` if it is part of the API, with what was sent the last time it was asked for
+    /// if the tree hasn't changed since.
+    fn get_api(
+        &mut self,
+        uri: &str,
+        accepts_gzip: bool,
+    ) -> Option<HttpResponse<Box<dyn io::Read>>> {
+        let cache = &mut self.api_cache;
+        if cache.modified != self.tree_modified || cache.bodies.len() >= MAX_CACHED_RESPONSES {
+            *cache = ApiCache::new(self.tree_modified);
+        }
+        let body = match cache.bodies.entry(uri.into()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match api::get(&self.tree, uri)? {
+                Ok(data) => entry.insert(CachedBody {
+                    data: data.into(),
+                    gzipped: None,
+                }),
+                Err((status, message)) => return Some(api::text_response(status, message)),
+            },
+        };
+        if accepts_gzip {
+            let data = &body.data;
+            let gzipped = body.gzipped.get_or_insert_with(|| gzip(data).into());
+            let mut response = gzip_response(HttpStatus::OK, gzipped.clone(), data.len());
+            api::add_json_headers(&mut response);
+            Some(response)
+        } else {
+            Some(api::json_data_response(body.data.clone()))
+        }
+    }
+
+    fn get(
         &mut self,
         uri: String,
-        stream: HttpBody<&mut I>,
+        headers: &HttpHeaders,
     ) -> Result<HttpResponse<Box<dyn io::Read>>> {
-        <TarHandler as HttpRequestHandler<I>>::put(&mut self.tar, uri, stream)
+        let accepts_gzip = accepts_gzip(headers);
+        let is_tree = uri.starts_with("/api/tree") || uri.starts_with("/api/search");
+        let validators = Validators::new(self.tree_modified, accepts_gzip);
+        if is_tree && validators.is_fresh(headers) {
+            let mut response = validators.not_modified();
+            add_vary(&mut response);
+            return Ok(response);
+        }
+
+        let mut response = if uri == "/api/rescan" {
+            return Ok(self.rescan_status());
+        } else if let Some(names) = api::endpoint_names(&uri, "/api/delete") {
+            return Ok(self.get_delete(names));
+        } else if let Some(response) = self.get_api(&uri, accepts_gzip) {
+            response
+        } else {
+            return Ok(self.tar.get(&uri, accepts_gzip));
+        };
+        if response.status == HttpStatus::OK {
+            validators.add_to(&mut response);
+        }
+        add_vary(&mut response);
+        Ok(response)
+    }
+
+    /// Responds to `request`.
+    pub fn handle<I: io::Read>(
+        &mut self,
+        request: HttpRequest<I>,
+    ) -> HttpResponse<Box<dyn io::Read>> {
+        if let Some(response) = self
+            .access_token
+            .as_ref()
+            .and_then(|t| t.check(&request.uri, &request.headers))
+        {
+            return response;
+        }
+        self.finish_rescan();
+        let response = match request.method {
+            HttpMethod::Get => self.get(request.uri, &request.headers),
+            HttpMethod::Post => Ok(self.post(&request.uri, &request.headers)),
+            _ => Ok(HttpResponse::from_string(
+                HttpStatus::MethodNotAllowed,
+                "method not allowed",
+            )),
+        };
+        response.unwrap_or_else(|e| e.into())
     }
 }
+
+#[cfg(test)]
+fn get_request(uri: &str, headers: &[(&str, &str)]) -> HttpRequest<io::Empty> {
+    let mut request = HttpRequest::new(HttpMethod::Get, uri);
+    for &(name, value) in headers {
+        request.add_header(name, value);
+    }
+    request
+}
+
+/// A POST request like the web client makes.
+#[cfg(test)]
+fn post_request(uri: &str, headers: &[(&str, &str)]) -> HttpRequest<io::Empty> {
+    let mut request = HttpRequest::new(HttpMethod::Post, uri);
+    request.add_header(API_REQUEST_HEADER, "1");
+    for &(name, value) in headers {
+        request.add_header(name, value);
+    }
+    request
+}
+
+#[test]
+fn tree_responses_can_be_revalidated() {
+    use std::path::Path;
+    use vdu_path_tree::NodeMetadata;
+
+    let mut tree = PathTree::empty();
+    tree.add_path(Path::new("/r"), 1, NodeMetadata::unknown());
+    let mut handler = VduHandler::new(tree, ServerOptions::default());
+
+    let response = handler.handle(get_request("/api/tree", &[]));
+    assert_eq!(response.status, HttpStatus::OK);
+    let etag = response.get_header("ETag").unwrap().to_owned();
+    let last_modified = response.get_header("Last-Modified").unwrap().to_owned();
+
+    for uri in &["/api/tree", "/api/tree?depth=1", "/api/search?pattern=r"] {
+        let response = handler.handle(get_request(uri, &[("If-None-Match", &etag)]));
+        assert_eq!(response.status, HttpStatus::NotModified);
+    }
+    let response = handler.handle(get_request(
+        "/api/tree",
+        &[("If-Modified-Since", &last_modified)],
+    ));
+    assert_eq!(response.status, HttpStatus::NotModified);
+
+    // the tree changing makes old copies stale
+    handler.tree_modified = SystemTime::now();
+    let response = handler.handle(get_request("/api/tree", &[("If-None-Match", &etag)]));
+    assert_eq!(response.status, HttpStatus::OK);
+    assert_ne!(response.get_header("ETag"), Some(&etag[..]));
+}
+
+#[test]
+fn tree_is_compressed_for_clients_accepting_gzip() {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::path::Path;
+    use vdu_path_tree::{NodeMetadata, DECODED_LENGTH_HEADER};
+
+    let mut tree = PathTree::empty();
+    tree.add_path(Path::new("/r"), 0, NodeMetadata::unknown());
+    for i in 0..100 {
+        let path = format!("/r/file-{}", i);
+        tree.add_path(Path::new(&path), i, NodeMetadata::unknown());
+    }
+    let mut handler = VduHandler::new(tree, ServerOptions::default());
+
+    for uri in &["/api/tree", "/api/search?pattern=file"] {
+        let mut plain = handler.handle(get_request(uri, &[]));
+        let mut data = vec![];
+        plain.body.read_to_end(&mut data).unwrap();
+        assert_eq!(plain.get_header("Content-Encoding"), None);
+        assert_eq!(plain.get_header("Content-Type"), Some("application/json"));
+        assert_eq!(plain.get_header("Vary"), Some("Accept-Encoding"));
+
+        for _ in 0..2 {
+            let mut gzipped = handler.handle(get_request(uri, &[("Accept-Encoding", "gzip")]));
+            assert_eq!(gzipped.get_header("Content-Encoding"), Some("gzip"));
+            assert_eq!(gzipped.get_header("Content-Type"), Some("application/json"));
+            assert_eq!(gzipped.get_header("Vary"), Some("Accept-Encoding"));
+            assert_eq!(
+                gzipped.get_header(DECODED_LENGTH_HEADER),
+                Some(&data.len().to_string()[..])
+            );
+            assert_ne!(gzipped.get_header("ETag"), plain.get_header("ETag"));
+
+            let mut decoded = vec![];
+            GzDecoder::new(&mut gzipped.body)
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, data);
+        }
+    }
+}
+
+#[test]
+fn api_responses_are_kept_until_tree_changes() {
+    use std::path::Path;
+    use vdu_path_tree::NodeMetadata;
+
+    let mut tree = PathTree::empty();
+    tree.add_path(Path::new("/r"), 1, NodeMetadata::unknown());
+    let mut handler = VduHandler::new(tree, ServerOptions::default());
+    let num_bytes = |handler: &mut VduHandler| -> u64 {
+        let response = handler.handle(get_request("/api/tree?depth=0", &[]));
+        let value: serde_json::Value = serde_json::from_reader(response.body).unwrap();
+        value["num_bytes"].as_u64().unwrap()
+    };
+    assert_eq!(num_bytes(&mut handler), 1);
+
+    handler
+        .tree
+        .add_path(Path::new("/r/a"), 2, NodeMetadata::unknown());
+    assert_eq!(num_bytes(&mut handler), 1);
+    handler.tree_modified = SystemTime::now();
+    assert_eq!(num_bytes(&mut handler), 3);
+
+    let missing = handler.handle(get_request("/api/tree/nope", &[]));
+    assert_eq!(missing.status, HttpStatus::NotFound);
+    assert_eq!(handler.api_cache.bodies.len(), 1);
+}
+
+#[test]
+fn rescan_swaps_in_new_tree() {
+    use std::path::Path;
+    use vdu_path_tree::NodeMetadata;
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut tree = PathTree::empty();
+    tree.add_path(&root, 1, NodeMetadata::unknown());
+    let options = ServerOptions {
+        scan_roots: vec![root.clone()],
+        ..ServerOptions::default()
+    };
+    let mut handler = VduHandler::new(tree, options);
+    let modified = handler.tree_modified;
+
+    let status = |response: HttpResponse<Box<dyn io::Read>>| -> RescanStatus {
+        serde_json::from_reader(response.body).unwrap()
+    };
+    let post = |uri: &str| post_request(uri, &[]);
+
+    let response = handler.handle(post("/api/rescan"));
+    assert_eq!(response.status, HttpStatus::Accepted);
+    assert_eq!(status(response).scanning, Some(root.display().to_string()));
+    assert_eq!(handler.start_rescan("").status, HttpStatus::Conflict);
+    while handler.rescan.is_some() {
+        std::thread::yield_now();
+        handler.finish_rescan();
+    }
+
+    let response = handler.handle(get_request("/api/rescan", &[]));
+    let status = status(response);
+    assert!(status.can_rescan);
+    assert_eq!(status.scanning, None);
+    assert_eq!(status.error, None);
+    assert!(handler.tree.get(&root.join("handler/vdu.rs")).is_some());
+    assert_ne!(handler.tree_modified, modified);
+
+    let mut handler = VduHandler::new(PathTree::empty(), ServerOptions::default());
+    let response = handler.handle(post("/api/rescan"));
+    assert_eq!(response.status, HttpStatus::Forbidden);
+}
+
+#[test]
+fn delete_needs_permission_and_confirmation() {
+    use std::fs;
+
+    let root = std::env::temp_dir().join(format!("vdu-delete-test-{}", std::process::id()));
+    let dir = root.join("dir");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("big"), vec![0; 1000]).unwrap();
+    fs::write(root.join("kept"), "kept").unwrap();
+    let tree = crate::walk::scan_in_background(&root, &Default::default()).unwrap();
+
+    let options = ServerOptions {
+        scan_roots: vec![root.clone()],
+        allow_delete: true,
+        delete_mode: DeleteMode::Permanent,
+        ..ServerOptions::default()
+    };
+    let mut handler = VduHandler::new(tree, options);
+    let delete = |confirm: Option<&str>| match confirm {
+        Some(path) => post_request("/api/delete/dir", &[(CONFIRM_DELETE_HEADER, path)]),
+        None => post_request("/api/delete/dir", &[]),
+    };
+
+    let preview = handler.handle(get_request("/api/delete/dir", &[]));
+    assert_eq!(preview.status, HttpStatus::OK);
+    let preview: vdu_path_tree::DeletePreview = serde_json::from_reader(preview.body).unwrap();
+    assert_eq!(preview.path, dir.display().to_string());
+    assert_eq!(preview.num_entries, 2);
+    assert_eq!(preview.largest, vec![("/big".to_owned(), 1000)]);
+
+    // the scanned path itself can't be deleted
+    let response = handler.handle(get_request("/api/delete", &[]));
+    let status: DeleteStatus = serde_json::from_reader(response.body).unwrap();
+    assert!(status.allowed);
+    let response = handler.handle(post_request("/api/delete/", &[]));
+    assert_eq!(response.status, HttpStatus::Forbidden);
+
+    let response = handler.handle(delete(None));
+    assert_eq!(response.status, HttpStatus::BadRequest);
+    assert!(dir.exists());
+
+    let num_bytes = handler.tree.num_bytes();
+    let response = handler.handle(delete(Some(&preview.path)));
+    assert_eq!(response.status, HttpStatus::OK);
+    assert!(!dir.exists());
+    assert!(handler.tree.get(&dir).is_none());
+    assert_eq!(handler.tree.num_bytes(), num_bytes - preview.num_bytes);
+
+    let mut handler = VduHandler::new(handler.tree, ServerOptions::default());
+    let response = handler.handle(get_request("/api/delete/kept", &[]));
+    assert_eq!(response.status, HttpStatus::Forbidden);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn trashed_entries_can_be_restored() {
+    use std::fs;
+
+    let root = std::env::temp_dir().join(format!("vdu-restore-test-{}", std::process::id()));
+    let scanned = root.join("scanned");
+    fs::create_dir_all(scanned.join("dir")).unwrap();
+    fs::write(scanned.join("dir/file"), vec![0; 100]).unwrap();
+    let tree = crate::walk::scan_in_background(&scanned, &Default::default()).unwrap();
+    let num_bytes = tree.num_bytes();
+
+    let options = ServerOptions {
+        scan_roots: vec![scanned.clone()],
+        allow_delete: true,
+        delete_mode: DeleteMode::Trash,
+        ..ServerOptions::default()
+    };
+    let mut handler = VduHandler::new(tree, options);
+    handler.home_trash = Some(root.join("data/Trash"));
+    let path = scanned.join("dir").display().to_string();
+    let request = post_request("/api/delete/dir", &[(CONFIRM_DELETE_HEADER, &path)]);
+    assert_eq!(handler.handle(request).status, HttpStatus::OK);
+    assert!(!scanned.join("dir").exists());
+    assert!(root.join("data/Trash/files/dir/file").exists());
+
+    let response = handler.handle(get_request("/api/delete", &[]));
+    let status: DeleteStatus = serde_json::from_reader(response.body).unwrap();
+    assert!(status.trash);
+    assert_eq!(status.trashed.len(), 1);
+    assert_eq!(status.trashed[0].path, path);
+
+    let restore = format!("/api/restore/{}", status.trashed[0].id);
+    // like a form on another site would send
+    let response = handler.handle(HttpRequest::new(HttpMethod::Post, &restore[..]));
+    assert_eq!(response.status, HttpStatus::Forbidden);
+    let response = handler.handle(post_request(&restore, &[]));
+    assert_eq!(response.status, HttpStatus::OK);
+    let status: DeleteStatus = serde_json::from_reader(response.body).unwrap();
+    assert!(status.trashed.is_empty());
+    assert_eq!(fs::read(scanned.join("dir/file")).unwrap().len(), 100);
+    assert_eq!(handler.tree.num_bytes(), num_bytes);
+
+    let response = handler.handle(post_request(&restore, &[]));
+    assert_eq!(response.status, HttpStatus::NotFound);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn requests_need_access_token() {
+    let options = ServerOptions {
+        token: Some("s3cret".into()),
+        ..ServerOptions::default()
+    };
+    let mut handler = VduHandler::new(PathTree::empty(), options);
+
+    for uri in &["/", "/api/tree", "/api/delete"] {
+        let response = handler.handle(get_request(uri, &[]));
+        assert_eq!(response.status, HttpStatus::Forbidden);
+    }
+    let post = HttpRequest::new(HttpMethod::Post, "/api/rescan");
+    assert_eq!(handler.handle(post).status, HttpStatus::Forbidden);
+
+    let response = handler.handle(get_request("/?token=s3cret", &[]));
+    assert_eq!(response.status, HttpStatus::SeeOther);
+    let cookie = response.get_header("Set-Cookie").unwrap();
+    let cookie = cookie.split(';').next().unwrap();
+    let response = handler.handle(get_request("/api/rescan", &[("Cookie", cookie)]));
+    assert_eq!(response.status, HttpStatus::OK);
+}
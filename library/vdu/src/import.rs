@@ -0,0 +1,283 @@
+// copyright 2021 Remi Bernotavicius
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use vdu_path_tree::{FileType, NodeMetadata, PathTree};
+
+/// The listing formats a `PathTree` can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// The output of `du -ab`: `<bytes>\t<path>`, directory sizes include their contents.
+    Du,
+    /// The output of `find -printf '%s %p\n'`: `<bytes> <path>`, one entry per file.
+    Find,
+    /// An ncdu JSON export (`ncdu -o`).
+    Ncdu,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "du" => Ok(Self::Du),
+            "find" => Ok(Self::Find),
+            "ncdu" => Ok(Self::Ncdu),
+            _ => Err(format!(
+                "unknown import format '{}', expected one of: du, find, ncdu",
+                s
+            )),
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Parses a `<bytes><separator><path>` line, returning `None` if it is malformed.
+fn parse_size_line(line: &str, separator: char) -> Option<(u64, PathBuf)> {
+    let (size, path) = line.split_once(separator)?;
+    let size = size.trim().parse().ok()?;
+    if path.is_empty() {
+        return None;
+    }
+    Some((size, PathBuf::from(path)))
+}
+
+fn read_size_lines(
+    input: impl BufRead,
+    separator: char,
+    expected: &str,
+) -> io::Result<BTreeMap<PathBuf, u64>> {
+    let mut entries = BTreeMap::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| invalid_data(format!("line {}: {}", number + 1, e)))?;
+        if line.is_empty() {
+            continue;
+        }
+
+        let (size, path) = parse_size_line(&line, separator).ok_or_else(|| {
+            invalid_data(format!(
+                "line {}: expected '{}', found '{}'",
+                number + 1,
+                expected,
+                line
+            ))
+        })?;
+        if entries.contains_key(&path) {
+            return Err(invalid_data(format!(
+                "line {}: '{}' is listed more than once",
+                number + 1,
+                path.display()
+            )));
+        }
+        entries.insert(path, size);
+    }
+    Ok(entries)
+}
+
+/// Builds a tree from entries holding the number of bytes each path uses by itself. Every entry
+/// other than the root must have its parent directory listed too.
+fn tree_from_entries(mut entries: BTreeMap<PathBuf, (u64, NodeMetadata)>) -> io::Result<PathTree> {
+    let mut tree = PathTree::empty();
+    let root = match entries.keys().next() {
+        Some(root) => root.clone(),
+        None => return Ok(tree),
+    };
+
+    for path in entries.keys().cloned().collect::<Vec<_>>() {
+        if path == root {
+            continue;
+        }
+        match path.parent().and_then(|p| entries.get_mut(p)) {
+            // Anything with children is a directory, even if the listing didn't say so.
+            Some((_, metadata)) if metadata.file_type == FileType::Unknown => {
+                metadata.file_type = FileType::Directory
+            }
+            Some(_) => {}
+            None => {
+                return Err(invalid_data(format!(
+                    "'{}' is not inside '{}' or its parent directory is not listed",
+                    path.display(),
+                    root.display()
+                )))
+            }
+        }
+    }
+
+    for (path, (num_bytes, metadata)) in &entries {
+        tree.add_path(path, *num_bytes, *metadata);
+    }
+    Ok(tree)
+}
+
+/// Imports the output of `du -ab`. `du` reports directories with the total size of their contents,
+/// so the size of each directory's own entry is recovered by subtracting its children.
+pub fn import_du(input: impl BufRead) -> io::Result<PathTree> {
+    let totals = read_size_lines(input, '\t', "<bytes>\\t<path>")?;
+
+    let mut entries: BTreeMap<_, _> = totals
+        .iter()
+        .map(|(path, &total)| (path.clone(), (total, NodeMetadata::unknown())))
+        .collect();
+    for (path, total) in &totals {
+        if let Some((own, _)) = path.parent().and_then(|p| entries.get_mut(p)) {
+            *own = own.saturating_sub(*total);
+        }
+    }
+    tree_from_entries(entries)
+}
+
+/// Imports the output of `find -printf '%s %p\n'`.
+pub fn import_find(input: impl BufRead) -> io::Result<PathTree> {
+    let entries = read_size_lines(input, ' ', "<bytes> <path>")?
+        .into_iter()
+        .map(|(path, num_bytes)| (path, (num_bytes, NodeMetadata::unknown())))
+        .collect();
+    tree_from_entries(entries)
+}
+
+fn ncdu_entry_info<'a>(
+    value: &'a Value,
+    parent: &Path,
+    is_dir: bool,
+) -> io::Result<(&'a str, u64, NodeMetadata)> {
+    let name = value.get("name").and_then(Value::as_str).ok_or_else(|| {
+        invalid_data(format!(
+            "ncdu entry in '{}' is missing a name: {}",
+            parent.display(),
+            value
+        ))
+    })?;
+    let num_bytes = value.get("asize").and_then(Value::as_u64).unwrap_or(0);
+
+    let file_type = if is_dir {
+        FileType::Directory
+    } else if value.get("notreg").and_then(Value::as_bool) == Some(true) {
+        FileType::Other
+    } else {
+        FileType::File
+    };
+    let metadata = NodeMetadata {
+        file_type,
+        modified: value.get("mtime").and_then(Value::as_u64),
+    };
+    Ok((name, num_bytes, metadata))
+}
+
+fn read_ncdu_entry(
+    value: &Value,
+    parent: &Path,
+    entries: &mut BTreeMap<PathBuf, (u64, NodeMetadata)>,
+) -> io::Result<()> {
+    // Directories are arrays of their own info followed by their children, files are just info.
+    let (info, children, is_dir) = match value {
+        Value::Array(items) if !items.is_empty() => (&items[0], &items[1..], true),
+        Value::Object(_) => (value, &[][..], false),
+        _ => {
+            return Err(invalid_data(format!(
+                "unexpected ncdu entry in '{}': {}",
+                parent.display(),
+                value
+            )))
+        }
+    };
+
+    let (name, num_bytes, metadata) = ncdu_entry_info(info, parent, is_dir)?;
+    let path = parent.join(name);
+    for child in children {
+        read_ncdu_entry(child, &path, entries)?;
+    }
+    entries.insert(path, (num_bytes, metadata));
+    Ok(())
+}
+
+/// Imports an ncdu JSON export, using the apparent size of each entry.
+pub fn import_ncdu(input: impl io::Read) -> io::Result<PathTree> {
+    let value: Value = serde_json::from_reader(input)
+        .map_err(|e| invalid_data(format!("invalid ncdu JSON: {}", e)))?;
+
+    // [majorver, minorver, {metadata}, root directory], without the root when the tree is empty
+    let root = match value.as_array().map(|a| &a[..]) {
+        Some([Value::Number(major), _, _, root]) if major.as_u64() == Some(1) => root,
+        Some([Value::Number(major), _, _]) if major.as_u64() == Some(1) => {
+            return Ok(PathTree::empty())
+        }
+        _ => {
+            return Err(invalid_data(
+                "not an ncdu export, expected [1, <minor>, {...}, [...]]".into(),
+            ))
+        }
+    };
+
+    let mut entries = BTreeMap::new();
+    read_ncdu_entry(root, Path::new(""), &mut entries)?;
+    tree_from_entries(entries)
+}
+
+pub fn import_tree(format: ImportFormat, input: impl BufRead) -> io::Result<PathTree> {
+    match format {
+        ImportFormat::Du => import_du(input),
+        ImportFormat::Find => import_find(input),
+        ImportFormat::Ncdu => import_ncdu(input),
+    }
+}
+
+#[test]
+fn import_du_subtracts_children() {
+    let input = "10\t/a/b/c\n15\t/a/b\n5\t/a/d\n24\t/a\n";
+    let tree = import_du(input.as_bytes()).unwrap();
+    assert_eq!(tree.size(), 4);
+    assert_eq!(tree.num_bytes(), 24);
+
+    let (name, b) = tree.children().find(|(n, _)| *n == "b").unwrap();
+    assert_eq!(name, "b");
+    assert_eq!(b.num_bytes(), 15);
+    assert_eq!(b.size(), 2);
+    assert_eq!(b.metadata().file_type, FileType::Directory);
+    assert_eq!(b.own_num_bytes(), 5);
+}
+
+#[test]
+fn import_find_handles_spaces_in_paths() {
+    let input = "4096 .\n4096 ./some dir\n7 ./some dir/a file\n";
+    let tree = import_find(input.as_bytes()).unwrap();
+    assert_eq!(tree.size(), 3);
+    assert_eq!(tree.num_bytes(), 4096 + 4096 + 7);
+    assert_eq!(tree.children().next().unwrap().0, "some dir");
+}
+
+#[test]
+fn import_find_reports_malformed_line() {
+    let input = "4096 .\nnot a size\n";
+    let error = import_find(input.as_bytes()).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().starts_with("line 2:"), "{}", error);
+}
+
+#[test]
+fn import_find_reports_missing_parent() {
+    let input = "1 /a\n1 /b/c\n";
+    let error = import_find(input.as_bytes()).err().unwrap();
+    assert!(error.to_string().contains("/b/c"), "{}", error);
+}
+
+#[test]
+fn import_ncdu_reads_nested_directories() {
+    let input = r#"[1, 2, {"progname": "ncdu", "progver": "1.15", "timestamp": 0},
+        [{"name": "/root", "asize": 4096},
+            {"name": "file", "asize": 100, "dsize": 4096},
+            [{"name": "dir", "asize": 4096}, {"name": "inner", "asize": 50}]]]"#;
+    let tree = import_ncdu(input.as_bytes()).unwrap();
+    assert_eq!(tree.size(), 4);
+    assert_eq!(tree.num_bytes(), 4096 + 100 + 4096 + 50);
+}
+
+#[test]
+fn import_ncdu_rejects_other_json() {
+    assert!(import_ncdu(&b"{\"name\": \"x\"}"[..]).is_err());
+}
@@ -1,17 +1,47 @@
 // copyright Remi Bernotavicius 2021
 
-use http_io::server::{HttpServer, Listen};
+use http_io::server::Listen;
 use std::io::Result;
+use std::path::PathBuf;
 
+pub use delete::{delete_path, is_inside, DeleteMode};
+pub use export::{export_csv, export_json, export_ncdu, export_tree, ExportFormat};
+pub use handler::random_token;
+pub use import::{import_du, import_find, import_ncdu, import_tree, ImportFormat};
+pub use report::{write_report, ReportOptions};
 use vdu_path_tree::PathTree;
 pub use walk::build_tree_from_path;
 
+mod delete;
+mod export;
 mod handler;
+mod import;
+mod report;
+mod trash;
 mod walk;
 
-pub fn run_server<S: Listen>(tree: PathTree, connection_stream: S) -> Result<()> {
-    let mut server = HttpServer::new(connection_stream, handler::VduHandler::new(tree));
+#[derive(Default)]
+pub struct ServerOptions {
+    /// The paths the tree was scanned from, which the web client can have scanned again. Empty
+    /// when the tree was imported.
+    pub scan_roots: Vec<PathBuf>,
+    /// Whether the web client may delete what is inside `scan_roots`.
+    pub allow_delete: bool,
+    pub delete_mode: DeleteMode,
+    /// The access token requests have to carry, in the query of the first one and a cookie after
+    /// that. `None` lets anyone who can connect in.
+    pub token: Option<String>,
+}
+
+pub fn run_server<S: Listen>(
+    tree: PathTree,
+    options: ServerOptions,
+    connection_stream: S,
+) -> Result<()> {
+    // requests are read here rather than by `HttpServer`, which only passes handlers the URI
+    let mut handler = handler::VduHandler::new(tree, options);
     loop {
-        server.serve_one()?
+        let mut stream = connection_stream.accept()?;
+        handler::serve_one(&mut handler, &mut stream)?
     }
 }
@@ -0,0 +1,182 @@
+// copyright 2021 Remi Bernotavicius
+
+use bytesize::ByteSize;
+use std::io::{self, Write};
+use vdu_path_tree::{PathTree, PathTreeNode};
+
+const BAR_WIDTH: usize = 20;
+
+pub struct ReportOptions {
+    /// How many levels below the root to show.
+    pub max_depth: usize,
+    /// Entries smaller than this are summarized in one line per directory.
+    pub min_bytes: u64,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 2,
+            min_bytes: 0,
+        }
+    }
+}
+
+fn percent(num_bytes: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        num_bytes as f64 / total as f64 * 100.0
+    }
+}
+
+fn bar(percent: f64) -> String {
+    let filled = ((percent / 100.0 * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    format!("[{}{}]", "#".repeat(filled), " ".repeat(BAR_WIDTH - filled))
+}
+
+fn write_line(
+    output: &mut impl Write,
+    depth: usize,
+    num_bytes: u64,
+    parent_bytes: u64,
+    name: &str,
+) -> io::Result<()> {
+    let percent = percent(num_bytes, parent_bytes);
+    writeln!(
+        output,
+        "{:>10} {:>5.1}% {} {}{}",
+        ByteSize::b(num_bytes).to_string(),
+        percent,
+        bar(percent),
+        "  ".repeat(depth),
+        name
+    )
+}
+
+fn display_name(name: &str, node: &PathTreeNode) -> String {
+    if node.is_directory() {
+        format!("{}/", name.trim_end_matches('/'))
+    } else {
+        name.into()
+    }
+}
+
+fn write_children(
+    node: &PathTreeNode,
+    depth: usize,
+    options: &ReportOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let mut children: Vec<_> = node.children().collect();
+    children.sort_by(|(a_name, a), (b_name, b)| {
+        b.num_bytes()
+            .cmp(&a.num_bytes())
+            .then_with(|| a_name.cmp(b_name))
+    });
+
+    let (shown, hidden): (Vec<_>, Vec<_>) = children
+        .into_iter()
+        .partition(|(_, c)| c.num_bytes() >= options.min_bytes);
+
+    for (name, child) in shown {
+        write_line(
+            output,
+            depth,
+            child.num_bytes(),
+            node.num_bytes(),
+            &display_name(name, child),
+        )?;
+        if depth < options.max_depth {
+            write_children(child, depth + 1, options, output)?;
+        }
+    }
+
+    if !hidden.is_empty() {
+        let hidden_bytes = hidden.iter().map(|(_, c)| c.num_bytes()).sum();
+        let plural = if hidden.len() == 1 {
+            "entry"
+        } else {
+            "entries"
+        };
+        let summary = format!("({} smaller {})", hidden.len(), plural);
+        write_line(output, depth, hidden_bytes, node.num_bytes(), &summary)?;
+    }
+    Ok(())
+}
+
+/// Writes the tree as indented lines sorted by size, each with the share of its parent directory.
+pub fn write_report(
+    tree: &PathTree,
+    options: &ReportOptions,
+    mut output: impl Write,
+) -> io::Result<()> {
+    if let Some(root) = tree.root() {
+        let name = display_name(&root.path().to_string_lossy(), root);
+        write_line(&mut output, 0, root.num_bytes(), root.num_bytes(), &name)?;
+        if options.max_depth > 0 {
+            write_children(root, 1, options, &mut output)?;
+        }
+    } else {
+        writeln!(output, "(empty)")?;
+    }
+    output.flush()
+}
+
+#[cfg(test)]
+fn test_report(options: &ReportOptions) -> String {
+    use std::path::Path;
+    use vdu_path_tree::NodeMetadata;
+
+    let mut tree = PathTree::empty();
+    for &(path, num_bytes) in &[
+        ("/r", 0),
+        ("/r/a", 100),
+        ("/r/b", 0),
+        ("/r/b/c", 250),
+        ("/r/b/d", 50),
+        ("/r/e", 600),
+    ] {
+        tree.add_path(Path::new(path), num_bytes, NodeMetadata::unknown());
+    }
+
+    let mut output = vec![];
+    write_report(&tree, options, &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn report_sorts_by_size() {
+    let report = test_report(&ReportOptions::default());
+    let names: Vec<_> = report
+        .lines()
+        .map(|l| l.split(']').nth(1).unwrap())
+        .collect();
+    assert_eq!(names, [" /r/", "   e", "   b/", "     c", "     d", "   a"]);
+    assert!(
+        report.starts_with(&format!("    1000 B 100.0% [{}] /r/", "#".repeat(20))),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn report_limits_depth() {
+    let options = ReportOptions {
+        max_depth: 1,
+        min_bytes: 0,
+    };
+    assert_eq!(test_report(&options).lines().count(), 4);
+}
+
+#[test]
+fn report_summarizes_small_entries() {
+    let options = ReportOptions {
+        max_depth: 2,
+        min_bytes: 200,
+    };
+    let report = test_report(&options);
+    let last = report.lines().last().unwrap();
+    assert!(last.ends_with("   (1 smaller entry)"), "{}", report);
+    assert!(last.contains(" 10.0% "), "{}", report);
+}
@@ -0,0 +1,361 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Moving things to the trash the way the freedesktop.org Trash specification describes, so they
+//! show up in file managers' trash and can be restored from there too.
+//!
+//! Things on the same file-system as the home directory go to `~/.local/share/Trash` (or
+//! `$XDG_DATA_HOME/Trash`), and things on other file-systems to a `.Trash/<uid>` or `.Trash-<uid>`
+//! directory at the top of their file-system, since they can't be moved across file-systems without
+//! copying.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Something that was moved to the trash.
+#[derive(Debug)]
+pub struct Trashed {
+    /// Where it was.
+    path: PathBuf,
+    /// Where it is in the trash.
+    file: PathBuf,
+    /// The `.trashinfo` file saying where it came from.
+    info: PathBuf,
+}
+
+impl Trashed {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves it back to where it was, unless something else has been put there since.
+    pub fn restore(&self) -> io::Result<()> {
+        if self.path.symlink_metadata().is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists again", self.path.display()),
+            ));
+        }
+        std::fs::rename(&self.file, &self.path)?;
+        std::fs::remove_file(&self.info)
+    }
+}
+
+/// Escapes what isn't allowed in a URI path, the way `Path=` in a `.trashinfo` is written.
+#[cfg(unix)]
+fn percent_encode(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let mut encoded = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// The current local time, like `2004-08-31T22:32:08`.
+#[cfg(unix)]
+fn deletion_date() -> String {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// The trash in the home directory.
+fn home_trash() -> io::Result<PathBuf> {
+    let data_home = match std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        Some(data_home) => PathBuf::from(data_home),
+        None => match std::env::var_os("HOME") {
+            Some(home) => Path::new(&home).join(".local/share"),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "HOME isn't set, so there is no trash",
+                ))
+            }
+        },
+    };
+    Ok(data_home.join("Trash"))
+}
+
+/// The top directory of the file-system `dir` is on.
+#[cfg(unix)]
+fn mount_point(dir: &Path, device: u64) -> io::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt as _;
+
+    let mut top = dir.canonicalize()?;
+    while let Some(parent) = top.parent() {
+        if parent.metadata()?.dev() != device {
+            break;
+        }
+        top = parent.to_owned();
+    }
+    Ok(top)
+}
+
+/// The device of the file-system `path` is on, or would be made on: that of its nearest ancestor
+/// that exists.
+#[cfg(unix)]
+fn nearest_device(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt as _;
+
+    let existing = path.ancestors().find(|a| a.exists()).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("nothing above {} exists", path.display()),
+        )
+    })?;
+    Ok(existing.metadata()?.dev())
+}
+
+/// Makes `dir` if it isn't there, then checks it is a directory of this user's, and not a symlink
+/// someone else put there to be sent what is trashed.
+#[cfg(unix)]
+fn own_directory(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt as _, MetadataExt as _};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    let metadata = dir.symlink_metadata()?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::getuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} isn't a directory of yours, so it can't be used as the trash",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// The trash at the top of a file-system: `$top/.Trash/$uid` if an administrator has made a
+/// `.Trash` for everyone, with the sticky bit so users can't touch each other's, otherwise
+/// `$top/.Trash-$uid`.
+#[cfg(unix)]
+fn top_trash(top: &Path) -> io::Result<PathBuf> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    const STICKY: u32 = 0o1000;
+
+    let uid = unsafe { libc::getuid() };
+    let shared = top.join(".Trash");
+    let is_sticky = shared
+        .symlink_metadata()
+        .is_ok_and(|m| m.is_dir() && m.permissions().mode() & STICKY != 0);
+    if is_sticky {
+        let trash = shared.join(uid.to_string());
+        match own_directory(&trash) {
+            Ok(()) => return Ok(trash),
+            Err(e) => log::warn!("not using {}: {}", trash.display(), e),
+        }
+    }
+
+    let trash = top.join(format!(".Trash-{}", uid));
+    own_directory(&trash)?;
+    Ok(trash)
+}
+
+/// The trash `path` should go to, and the path to record for it there: the absolute path in the
+/// home trash, or the path from the top of the file-system in a file-system's own trash.
+#[cfg(unix)]
+fn trash_for(path: &Path, home_trash: PathBuf) -> io::Result<(PathBuf, PathBuf)> {
+    use std::os::unix::fs::MetadataExt as _;
+
+    let parent = match path.parent() {
+        Some(parent) if path.file_name().is_some() => parent.canonicalize()?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} can't be moved to the trash", path.display()),
+            ))
+        }
+    };
+    let path = parent.join(path.file_name().unwrap());
+    let device = path.symlink_metadata()?.dev();
+
+    if nearest_device(&home_trash)? == device {
+        return Ok((home_trash, path));
+    }
+
+    let top = mount_point(&parent, device)?;
+    let trash = top_trash(&top)?;
+    let relative = path.strip_prefix(&top).unwrap().to_owned();
+    Ok((trash, relative))
+}
+
+/// Moves `path` to the trash.
+pub fn move_to_trash(path: &Path) -> io::Result<Trashed> {
+    move_to_trash_with_home(path, home_trash()?)
+}
+
+#[cfg(not(unix))]
+pub fn move_to_trash_with_home(_path: &Path, _home_trash: PathBuf) -> io::Result<Trashed> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "there is no trash on this platform",
+    ))
+}
+
+/// Moves `path` to the trash, with `home_trash` as the trash in the home directory.
+#[cfg(unix)]
+pub fn move_to_trash_with_home(path: &Path, home_trash: PathBuf) -> io::Result<Trashed> {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write as _;
+    use std::os::unix::fs::{DirBuilderExt as _, OpenOptionsExt as _};
+
+    let (trash, recorded_path) = trash_for(path, home_trash)?;
+    let files = trash.join("files");
+    let info = trash.join("info");
+    for dir in &[&trash, &files, &info] {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+
+    // the name is claimed by creating its .trashinfo, which fails if another program got there
+    // first
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    let (name, mut info_file) = (1..)
+        .map(|n| match n {
+            1 => name.clone(),
+            n => format!("{}.{}", name, n),
+        })
+        .filter(|name| files.join(name).symlink_metadata().is_err())
+        .find_map(|name| {
+            let info_file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(info.join(format!("{}.trashinfo", name)));
+            match info_file {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                info_file => Some(info_file.map(|f| (name, f))),
+            }
+        })
+        .unwrap()?;
+
+    let trashed = Trashed {
+        path: path.to_owned(),
+        file: files.join(&name),
+        info: info.join(format!("{}.trashinfo", name)),
+    };
+    let moved = write!(
+        info_file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(&recorded_path),
+        deletion_date()
+    )
+    .and_then(|()| fs::rename(path, &trashed.file));
+    if let Err(e) = moved {
+        let _ = fs::remove_file(&trashed.info);
+        return Err(e);
+    }
+    Ok(trashed)
+}
+
+#[cfg(unix)]
+#[test]
+fn trashinfo_path_is_percent_encoded() {
+    assert_eq!(
+        percent_encode(Path::new("/home/me/my file%.txt")),
+        "/home/me/my%20file%25.txt"
+    );
+    assert_eq!(percent_encode(Path::new("/é")), "/%C3%A9");
+}
+
+#[cfg(unix)]
+#[test]
+fn top_trash_is_checked() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let top = std::env::temp_dir().join(format!("vdu-top-trash-test-{}", std::process::id()));
+    fs::create_dir_all(&top).unwrap();
+    let uid = unsafe { libc::getuid() };
+    let own = top.join(format!(".Trash-{}", uid));
+
+    // without a shared .Trash with the sticky bit, the user's own one is made
+    fs::create_dir(top.join(".Trash")).unwrap();
+    assert_eq!(top_trash(&top).unwrap(), own);
+    assert!(own.is_dir());
+
+    let shared = top.join(".Trash");
+    fs::set_permissions(&shared, fs::Permissions::from_mode(0o1777)).unwrap();
+    assert_eq!(top_trash(&top).unwrap(), shared.join(uid.to_string()));
+
+    // a symlink put in place of either is never followed
+    fs::remove_dir(shared.join(uid.to_string())).unwrap();
+    std::os::unix::fs::symlink(&own, shared.join(uid.to_string())).unwrap();
+    assert_eq!(top_trash(&top).unwrap(), own);
+    fs::remove_dir(&own).unwrap();
+    std::os::unix::fs::symlink(top.join("elsewhere"), &own).unwrap();
+    let error = top_trash(&top).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+
+    fs::remove_dir_all(&top).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn trashed_files_can_be_restored() {
+    use std::fs;
+
+    let root = std::env::temp_dir().join(format!("vdu-trash-test-{}", std::process::id()));
+    let trash = root.join("Trash");
+    let dir = root.join("dir");
+    fs::create_dir_all(&dir).unwrap();
+
+    let file = dir.join("a file");
+    fs::write(&file, "contents").unwrap();
+    // the home trash is only made once something is moved to it
+    let (home, _) = trash_for(&file, trash.clone()).unwrap();
+    assert_eq!(home, trash);
+    assert!(!trash.exists());
+
+    let mut trashed = vec![];
+    for _ in 0..2 {
+        fs::write(&file, "contents").unwrap();
+        trashed.push(move_to_trash_with_home(&file, trash.clone()).unwrap());
+        assert!(!file.exists());
+    }
+    assert_eq!(trashed[0].file, trash.join("files/a file"));
+    assert_eq!(trashed[1].file, trash.join("files/a file.2"));
+
+    let info = fs::read_to_string(trash.join("info/a file.2.trashinfo")).unwrap();
+    let mut lines = info.lines();
+    assert_eq!(lines.next(), Some("[Trash Info]"));
+    assert_eq!(
+        lines.next(),
+        Some(
+            &format!(
+                "Path={}",
+                percent_encode(&dir.canonicalize().unwrap().join("a file"))
+            )[..]
+        )
+    );
+    assert!(lines.next().unwrap().starts_with("DeletionDate="));
+
+    trashed[1].restore().unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "contents");
+    assert!(!trash.join("info/a file.2.trashinfo").exists());
+    let error = trashed[0].restore().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+
+    fs::remove_dir_all(&root).unwrap();
+}
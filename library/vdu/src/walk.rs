@@ -6,7 +6,9 @@ use num_format::{Locale, ToFormattedString as _};
 use std::fs::Metadata;
 use std::io;
 use std::path::{Path, PathBuf};
-use vdu_path_tree::PathTree;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+use vdu_path_tree::{FileType, NodeMetadata, PathTree};
 use walkdir::WalkDir;
 
 fn log_path_error(path: &Path) {
@@ -27,9 +29,30 @@ fn error_logging_walk(path: &Path) -> impl Iterator<Item = (PathBuf, Metadata)>
         })
 }
 
-pub fn build_tree_from_path(path: &Path) -> io::Result<PathTree> {
-    log::info!("scanning \"{}\"", path.display());
+fn node_metadata(meta: &Metadata) -> NodeMetadata {
+    let file_type = meta.file_type();
+    let file_type = if file_type.is_dir() {
+        FileType::Directory
+    } else if file_type.is_file() {
+        FileType::File
+    } else if file_type.is_symlink() {
+        FileType::Symlink
+    } else {
+        FileType::Other
+    };
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    NodeMetadata {
+        file_type,
+        modified,
+    }
+}
 
+/// Walks `path`, calling `on_entry` after adding each entry to the tree.
+fn walk_into_tree(path: &Path, mut on_entry: impl FnMut()) -> io::Result<PathTree> {
     if !path.exists() {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
@@ -37,20 +60,27 @@ pub fn build_tree_from_path(path: &Path) -> io::Result<PathTree> {
         ));
     }
 
-    let prog_style = ProgressStyle::default_spinner().template("{spinner} {wide_msg}");
-    let prog = ProgressBar::new_spinner();
-    prog.set_style(prog_style);
-
     let mut path_tree = PathTree::empty();
-
     for (path, meta) in error_logging_walk(path) {
         let num_bytes = meta.len();
-        path_tree.add_path(&path, num_bytes);
+        path_tree.add_path(&path, num_bytes, node_metadata(&meta));
+        on_entry();
+    }
+    Ok(path_tree)
+}
+
+pub fn build_tree_from_path(path: &Path) -> io::Result<PathTree> {
+    log::info!("scanning \"{}\"", path.display());
 
+    let prog_style = ProgressStyle::default_spinner().template("{spinner} {wide_msg}");
+    let prog = ProgressBar::new_spinner();
+    prog.set_style(prog_style);
+
+    let path_tree = walk_into_tree(path, || {
         prog.inc(1);
         let message = format!("{} files", prog.position().to_formatted_string(&Locale::en));
         prog.set_message(message);
-    }
+    })?;
 
     log::info!(
         "found {} files",
@@ -60,3 +90,12 @@ pub fn build_tree_from_path(path: &Path) -> io::Result<PathTree> {
 
     Ok(path_tree)
 }
+
+/// Like `build_tree_from_path`, but counts the entries found in `entries` instead of showing a
+/// spinner, for scanning while the server is running.
+pub fn scan_in_background(path: &Path, entries: &AtomicU64) -> io::Result<PathTree> {
+    log::info!("scanning \"{}\" again", path.display());
+    walk_into_tree(path, || {
+        entries.fetch_add(1, Ordering::Relaxed);
+    })
+}
@@ -0,0 +1,108 @@
+// Copyright 2021 Remi Bernotavicius
+
+use crate::search::path_from_root;
+use crate::PathTreeNode;
+use serde::{Deserialize, Serialize};
+
+/// What the server says about deleting entries from the web client.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DeleteStatus {
+    /// False unless the server was started with `--allow-delete`.
+    pub allowed: bool,
+    /// Whether deleting moves things to the trash rather than removing them for good.
+    pub trash: bool,
+    /// What has been moved to the trash since the server started, most recent last, for undoing.
+    pub trashed: Vec<TrashedEntry>,
+}
+
+/// Something moved to the trash.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TrashedEntry {
+    /// What the server knows it by, for restoring it.
+    pub id: u64,
+    /// Where it was on disk.
+    pub path: String,
+    pub num_bytes: u64,
+}
+
+/// What deleting an entry would remove, shown before asking whether to go ahead.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DeletePreview {
+    /// Where the entry is on disk.
+    pub path: String,
+    pub num_bytes: u64,
+    /// How many entries would be removed, counting the entry itself.
+    pub num_entries: usize,
+    /// The largest of the entries without children beneath it, largest first, as given by
+    /// `path_from_root` from the entry.
+    pub largest: Vec<(String, u64)>,
+    /// Whether it would be moved to the trash rather than removed for good.
+    pub trash: bool,
+}
+
+fn leaves<'a>(node: &'a PathTreeNode, out: &mut Vec<&'a PathTreeNode>) {
+    for (_, child) in node.children() {
+        if child.children().next().is_none() {
+            out.push(child);
+        } else {
+            leaves(child, out);
+        }
+    }
+}
+
+impl PathTreeNode {
+    /// What deleting this entry would remove, listing at most `max_largest` of what is in it.
+    /// `trash` says whether it would go to the trash.
+    pub fn delete_preview(&self, max_largest: usize, trash: bool) -> DeletePreview {
+        let mut largest = vec![];
+        leaves(self, &mut largest);
+        largest.sort_by(|a, b| {
+            b.num_bytes()
+                .cmp(&a.num_bytes())
+                .then(a.path().cmp(b.path()))
+        });
+        DeletePreview {
+            path: self.path().display().to_string(),
+            num_bytes: self.num_bytes(),
+            num_entries: self.size(),
+            largest: largest
+                .into_iter()
+                .take(max_largest)
+                .map(|n| (path_from_root(self.path(), n.path()), n.num_bytes()))
+                .collect(),
+            trash,
+        }
+    }
+}
+
+#[test]
+fn delete_preview_lists_largest_entries() {
+    use std::path::Path;
+
+    let tree = crate::test_tree(&[
+        ("/r", 0),
+        ("/r/a", 1),
+        ("/r/a/b", 5),
+        ("/r/a/c", 0),
+        ("/r/a/c/d", 3),
+        ("/r/e", 4),
+    ]);
+    let preview = tree
+        .get(Path::new("/r/a"))
+        .unwrap()
+        .delete_preview(2, false);
+    assert_eq!(preview.path, "/r/a");
+    assert_eq!(preview.num_bytes, 9);
+    assert_eq!(preview.num_entries, 4);
+    assert_eq!(
+        preview.largest,
+        vec![("/b".to_owned(), 5), ("/c/d".to_owned(), 3)]
+    );
+
+    let preview = tree
+        .get(Path::new("/r/e"))
+        .unwrap()
+        .delete_preview(2, false);
+    assert_eq!(preview.num_entries, 1);
+    assert!(preview.largest.is_empty());
+}
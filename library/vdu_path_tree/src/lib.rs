@@ -1,36 +1,143 @@
 // Copyright 2021 Remi Bernotavicius
 
 use serde::{Deserialize, Serialize};
-use std::collections::{hash_map, HashMap};
+use std::collections::hash_map::{self, Entry};
+use std::collections::HashMap;
 use std::fmt;
 use std::path::{Path, PathBuf};
 
+pub use deletion::{DeletePreview, DeleteStatus, TrashedEntry};
+pub use search::{Query, SearchMode, SearchResponse};
+pub use subtree::Subtree;
+
+mod deletion;
+mod search;
+mod subtree;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+    Other,
+    /// The source of the entry didn't say what it was.
+    Unknown,
+}
+
+impl FileType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Directory => "directory",
+            Self::Symlink => "symlink",
+            Self::Other => "other",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Information about an entry other than its size.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeMetadata {
+    pub file_type: FileType,
+    /// Modification time in seconds since the unix epoch.
+    pub modified: Option<u64>,
+}
+
+impl NodeMetadata {
+    pub fn unknown() -> Self {
+        Self {
+            file_type: FileType::Unknown,
+            modified: None,
+        }
+    }
+}
+
+/// Formats seconds since the unix epoch as a UTC date and time.
+pub fn format_timestamp(secs: u64) -> String {
+    // Howard Hinnant's civil_from_days
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let secs_of_day = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60
+    )
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PathTreeNode {
     path: PathBuf,
     num_descendants: usize,
     num_bytes: u64,
+    metadata: NodeMetadata,
     children: HashMap<String, Box<PathTreeNode>>,
 }
 
 impl PathTreeNode {
-    fn new(path: &Path, num_bytes: u64) -> Self {
+    fn new(path: &Path, num_bytes: u64, metadata: NodeMetadata) -> Self {
         Self {
             path: path.to_owned(),
             num_descendants: 0,
             num_bytes,
+            metadata,
             children: HashMap::new(),
         }
     }
 
+    /// A directory that only exists to hold other entries.
+    fn directory(path: &Path) -> Self {
+        let metadata = NodeMetadata {
+            file_type: FileType::Directory,
+            modified: None,
+        };
+        Self::new(path, 0, metadata)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn metadata(&self) -> NodeMetadata {
+        self.metadata
+    }
+
     pub fn size(&self) -> usize {
         self.num_descendants + 1
     }
 
+    /// Whether it is a directory, going by its contents when the listing it came from didn't say.
+    /// Empty directories and ones whose contents haven't been loaded are still directories.
+    pub fn is_directory(&self) -> bool {
+        self.metadata.file_type == FileType::Directory || self.size() > 1
+    }
+
     pub fn num_bytes(&self) -> u64 {
         self.num_bytes
     }
 
+    /// The number of bytes used by this entry, not counting its descendants.
+    pub fn own_num_bytes(&self) -> u64 {
+        self.num_bytes - self.children.values().map(|c| c.num_bytes).sum::<u64>()
+    }
+
     pub fn children<'a>(&'a self) -> ChildrenForNode<'a> {
         ChildrenForNode {
             node: self,
@@ -38,23 +145,30 @@ impl PathTreeNode {
         }
     }
 
-    fn add_path(&mut self, path: &Path, num_bytes: u64) -> bool {
+    /// The children ordered by name, for when output needs to be stable.
+    pub fn sorted_children(&self) -> Vec<(&str, &PathTreeNode)> {
+        let mut children: Vec<_> = self.children().collect();
+        children.sort_by_key(|&(name, _)| name);
+        children
+    }
+
+    fn add_path(&mut self, path: &Path, num_bytes: u64, metadata: NodeMetadata) -> bool {
         if let Ok(sub_path) = path.strip_prefix(&self.path) {
             let mut iter = sub_path.iter();
             if let Some(next) = iter.next() {
                 if iter.next().is_none() {
                     self.children.insert(
                         next.to_str().unwrap().into(),
-                        Box::new(PathTreeNode::new(path, num_bytes)),
+                        Box::new(PathTreeNode::new(path, num_bytes, metadata)),
                     );
                     self.num_descendants += 1;
-                    self.num_bytes = self.num_bytes + num_bytes;
+                    self.num_bytes += num_bytes;
                     return true;
                 }
                 if let Some(c) = self.children.get_mut(next.to_str().unwrap()) {
-                    if c.add_path(path, num_bytes) {
+                    if c.add_path(path, num_bytes, metadata) {
                         self.num_descendants += 1;
-                        self.num_bytes = self.num_bytes + num_bytes;
+                        self.num_bytes += num_bytes;
                         return true;
                     }
                 }
@@ -63,6 +177,112 @@ impl PathTreeNode {
 
         false
     }
+
+    fn get(&self, path: &Path) -> Option<&PathTreeNode> {
+        let sub_path = path.strip_prefix(&self.path).ok()?;
+        sub_path.iter().try_fold(self, |node, name| {
+            node.children.get(name.to_str()?).map(|c| &**c)
+        })
+    }
+
+    fn remove_path(&mut self, path: &Path) -> Option<Box<PathTreeNode>> {
+        let sub_path = path.strip_prefix(&self.path).ok()?;
+        let mut iter = sub_path.iter();
+        let next = iter.next()?.to_str()?;
+        let removed = if iter.next().is_none() {
+            self.children.remove(next)?
+        } else {
+            self.children.get_mut(next)?.remove_path(path)?
+        };
+        self.num_bytes -= removed.num_bytes;
+        self.num_descendants -= removed.size();
+        Some(removed)
+    }
+
+    fn update_totals(&mut self, own_num_bytes: u64) {
+        self.num_bytes = own_num_bytes + self.children.values().map(|c| c.num_bytes).sum::<u64>();
+        self.num_descendants = self.children.values().map(|c| c.size()).sum();
+    }
+
+    /// Merges `other`, which must have the same path, into this node. Where both have an entry
+    /// for the same path, the size and metadata of the one from `other` is kept.
+    fn merge(&mut self, other: PathTreeNode) {
+        let own_num_bytes = other.own_num_bytes();
+        for (name, child) in other.children {
+            match self.children.entry(name) {
+                Entry::Occupied(mut e) => e.get_mut().merge(*child),
+                Entry::Vacant(e) => {
+                    e.insert(child);
+                }
+            }
+        }
+        self.metadata = other.metadata;
+        self.update_totals(own_num_bytes);
+    }
+
+    /// Places `other` somewhere beneath this node, creating any directories in between.
+    fn graft(&mut self, other: PathTreeNode) {
+        let sub_path = other.path.strip_prefix(&self.path).unwrap().to_owned();
+        let mut iter = sub_path.iter();
+        let next = match iter.next() {
+            Some(next) => next.to_str().unwrap(),
+            None => return self.merge(other),
+        };
+
+        let own_num_bytes = self.own_num_bytes();
+        let is_child = iter.next().is_none();
+        match self.children.entry(next.into()) {
+            Entry::Occupied(mut e) => e.get_mut().graft(other),
+            Entry::Vacant(e) if is_child => {
+                e.insert(Box::new(other));
+            }
+            Entry::Vacant(e) => e
+                .insert(Box::new(PathTreeNode::directory(&self.path.join(next))))
+                .graft(other),
+        }
+        self.update_totals(own_num_bytes);
+    }
+}
+
+fn common_ancestor(a: &Path, b: &Path) -> PathBuf {
+    a.components()
+        .zip(b.components())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a)
+        .collect()
+}
+
+/// Changed whenever the serialized form of `PathTree`, or of what the server's API sends, changes,
+/// so the web client can tell it is talking to a server it can't understand.
+pub const FORMAT_VERSION: u32 = 3;
+
+/// The response header the server sends `FORMAT_VERSION` in.
+pub const FORMAT_VERSION_HEADER: &str = "X-Vdu-Format-Version";
+
+/// The response header with the length of a compressed body once it is decompressed.
+pub const DECODED_LENGTH_HEADER: &str = "X-Vdu-Decoded-Length";
+
+/// The request header the web client sends with every request that changes something, like
+/// starting a scan. Browsers don't let other sites send it without asking the server first, which
+/// it doesn't answer, so they can't make those requests with a plain form.
+pub const API_REQUEST_HEADER: &str = "X-Vdu-Request";
+
+/// The request header the web client confirms a deletion with, holding the percent-encoded path
+/// of what is being deleted. Browsers don't let other sites send it without asking the server
+/// first, which it doesn't answer.
+pub const CONFIRM_DELETE_HEADER: &str = "X-Vdu-Confirm-Delete";
+
+/// What the server says about scanning the tree again.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RescanStatus {
+    /// False when the tree was imported rather than scanned, so there is nothing to scan.
+    pub can_rescan: bool,
+    /// The path being scanned, while a scan is running.
+    pub scanning: Option<String>,
+    /// How many entries the running scan has found so far.
+    pub entries: u64,
+    /// Why the last scan failed, if it did.
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -145,15 +365,175 @@ impl PathTree {
         }
     }
 
-    pub fn add_path(&mut self, path: &Path, num_bytes: u64) {
+    pub fn add_path(&mut self, path: &Path, num_bytes: u64, metadata: NodeMetadata) {
         if let Some(root) = &mut self.root {
-            assert!(root.add_path(path, num_bytes));
+            assert!(root.add_path(path, num_bytes, metadata));
         } else {
-            self.root = Some(Box::new(PathTreeNode::new(path, num_bytes)));
+            self.root = Some(Box::new(PathTreeNode::new(path, num_bytes, metadata)));
         }
     }
 
+    pub fn root(&self) -> Option<&PathTreeNode> {
+        self.root.as_deref()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&PathTreeNode> {
+        self.root.as_ref().and_then(|root| root.get(path))
+    }
+
+    /// Removes the entry at `path` and everything beneath it, returning what was removed as its
+    /// own tree.
+    pub fn remove_path(&mut self, path: &Path) -> Option<PathTree> {
+        let root = self.root.as_mut()?;
+        let removed = if root.path == path {
+            self.root.take()?
+        } else {
+            root.remove_path(path)?
+        };
+        Some(PathTree {
+            root: Some(removed),
+        })
+    }
+
+    /// Combines `other` into this tree. The root becomes the deepest directory containing both
+    /// roots, or a root with an empty path when they have nothing in common (e.g. two relative
+    /// paths). Where both trees have an entry for the same path, the size and metadata from
+    /// `other` are kept and the children of both are combined, so merging trees in the same order
+    /// always gives the same result.
+    pub fn merge(&mut self, other: PathTree) {
+        let other_root = match other.root {
+            Some(root) => root,
+            None => return,
+        };
+        let root = match self.root.take() {
+            Some(root) => root,
+            None => {
+                self.root = Some(other_root);
+                return;
+            }
+        };
+
+        let ancestor = common_ancestor(&root.path, &other_root.path);
+        let mut new_root = if root.path == ancestor {
+            root
+        } else {
+            let mut new_root = Box::new(PathTreeNode::directory(&ancestor));
+            new_root.graft(*root);
+            new_root
+        };
+        new_root.graft(*other_root);
+        self.root = Some(new_root);
+    }
+
     pub fn children<'a>(&'a self) -> Children<'a> {
         Children(self.root.as_ref().map(|n| n.children()))
     }
 }
+
+#[cfg(test)]
+fn test_tree(paths: &[(&str, u64)]) -> PathTree {
+    let mut tree = PathTree::empty();
+    for &(path, num_bytes) in paths {
+        tree.add_path(Path::new(path), num_bytes, NodeMetadata::unknown());
+    }
+    tree
+}
+
+#[test]
+fn merge_uses_common_ancestor() {
+    let mut tree = test_tree(&[("/srv", 1), ("/srv/a", 2)]);
+    tree.merge(test_tree(&[("/var/log", 3), ("/var/log/b", 4)]));
+
+    let root = tree.root().unwrap();
+    assert_eq!(root.path(), Path::new("/"));
+    assert_eq!(tree.num_bytes(), 10);
+    assert_eq!(tree.size(), 6);
+
+    let var = root.children().find(|&(n, _)| n == "var").unwrap().1;
+    assert_eq!(var.path(), Path::new("/var"));
+    assert_eq!(var.own_num_bytes(), 0);
+    assert_eq!(var.size(), 3);
+}
+
+#[test]
+fn merge_into_existing_directory() {
+    let mut tree = test_tree(&[("/srv", 1), ("/srv/a", 2)]);
+    tree.merge(test_tree(&[("/srv/a/b", 3)]));
+
+    assert_eq!(tree.root().unwrap().path(), Path::new("/srv"));
+    assert_eq!(tree.num_bytes(), 6);
+    assert_eq!(tree.size(), 3);
+}
+
+#[test]
+fn merge_overlapping_prefers_other() {
+    let mut tree = test_tree(&[("/srv", 1), ("/srv/a", 2), ("/srv/b", 3)]);
+    tree.merge(test_tree(&[("/srv", 10), ("/srv/a", 20), ("/srv/c", 30)]));
+
+    assert_eq!(tree.num_bytes(), 10 + 20 + 3 + 30);
+    assert_eq!(tree.size(), 4);
+    assert_eq!(tree.root().unwrap().own_num_bytes(), 10);
+}
+
+#[test]
+fn merge_unrelated_uses_empty_root() {
+    let mut tree = test_tree(&[("a", 1)]);
+    tree.merge(test_tree(&[("b", 2)]));
+
+    let root = tree.root().unwrap();
+    assert_eq!(root.path(), Path::new(""));
+    assert_eq!(root.sorted_children().len(), 2);
+    assert_eq!(tree.num_bytes(), 3);
+}
+
+#[test]
+fn merge_into_empty() {
+    let mut tree = PathTree::empty();
+    tree.merge(test_tree(&[("/a", 1)]));
+    assert_eq!(tree.num_bytes(), 1);
+    tree.merge(PathTree::empty());
+    assert_eq!(tree.num_bytes(), 1);
+}
+
+#[test]
+fn is_directory_goes_by_type_or_contents() {
+    let mut tree = test_tree(&[("/srv", 1), ("/srv/a", 2), ("/srv/a/b", 3)]);
+    let dir = NodeMetadata {
+        file_type: FileType::Directory,
+        modified: None,
+    };
+    tree.add_path(Path::new("/srv/empty"), 0, dir);
+    assert!(tree.get(Path::new("/srv/a")).unwrap().is_directory());
+    assert!(tree.get(Path::new("/srv/empty")).unwrap().is_directory());
+    assert!(!tree.get(Path::new("/srv/a/b")).unwrap().is_directory());
+}
+
+#[test]
+fn format_timestamp_gives_utc_date() {
+    assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+    assert_eq!(format_timestamp(1_614_865_500), "2021-03-04 13:45 UTC");
+    assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00 UTC");
+}
+
+#[test]
+fn get_finds_nested_entries() {
+    let tree = test_tree(&[("/srv", 1), ("/srv/a", 2), ("/srv/a/b", 3)]);
+    assert_eq!(tree.get(Path::new("/srv/a/b")).unwrap().num_bytes(), 3);
+    assert_eq!(tree.get(Path::new("/srv")).unwrap().num_bytes(), 6);
+    assert!(tree.get(Path::new("/srv/c")).is_none());
+    assert!(tree.get(Path::new("/var")).is_none());
+}
+
+#[test]
+fn remove_path_updates_totals() {
+    let mut tree = test_tree(&[("/srv", 1), ("/srv/a", 2), ("/srv/a/b", 3), ("/srv/c", 4)]);
+    let removed = tree.remove_path(Path::new("/srv/a")).unwrap();
+    assert_eq!(removed.num_bytes(), 5);
+    assert_eq!(removed.size(), 2);
+    assert_eq!(tree.num_bytes(), 5);
+    assert_eq!(tree.size(), 2);
+
+    assert!(tree.remove_path(Path::new("/srv/a")).is_none());
+    tree.merge(removed);
+    assert_eq!(tree.num_bytes(), 10);
+}
@@ -0,0 +1,268 @@
+// Copyright 2021 Remi Bernotavicius
+
+use crate::{PathTree, PathTreeNode};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+/// How the text being searched for is interpreted. Paths are searched from the root of the tree,
+/// as given by `path_from_root`, so the directory the tree was scanned from doesn't match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Paths containing the text, ignoring case.
+    Substring,
+    /// Shell style patterns with `*`, `?`, `[...]` and `**`. Patterns without a `/` are matched
+    /// against the name only, like `find -name`.
+    Glob,
+    Regex,
+}
+
+impl FromStr for SearchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "substring" => Ok(Self::Substring),
+            "glob" => Ok(Self::Glob),
+            "regex" => Ok(Self::Regex),
+            _ => Err(format!("unknown search mode '{}'", s)),
+        }
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` can match no directories at all, so `/a/**/b` matches `/a/b`
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                let rest: String = chars.clone().collect();
+                let class = match rest.find(']') {
+                    Some(end) => &rest[..end],
+                    None => {
+                        re.push_str(r"\[");
+                        continue;
+                    }
+                };
+                // skip past the class and its closing bracket
+                chars.nth(class.chars().count());
+
+                re.push('[');
+                let class = match class.strip_prefix('!') {
+                    Some(rest) => {
+                        re.push('^');
+                        rest
+                    }
+                    None => class,
+                };
+                for c in class.chars() {
+                    if matches!(c, '\\' | '[' | '^') {
+                        re.push('\\');
+                    }
+                    re.push(c);
+                }
+                re.push(']');
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// Something to look for in the paths of a tree.
+#[derive(Debug)]
+pub struct Query {
+    matcher: Matcher,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    /// Already lowercase.
+    Substring(String),
+    Regex {
+        regex: Regex,
+        name_only: bool,
+    },
+}
+
+impl Query {
+    pub fn new(mode: SearchMode, pattern: &str) -> Result<Self, regex::Error> {
+        let matcher = match mode {
+            SearchMode::Substring => Matcher::Substring(pattern.to_lowercase()),
+            SearchMode::Glob => Matcher::Regex {
+                regex: Regex::new(&glob_to_regex(pattern))?,
+                name_only: !pattern.contains('/'),
+            },
+            SearchMode::Regex => Matcher::Regex {
+                regex: Regex::new(pattern)?,
+                name_only: false,
+            },
+        };
+        Ok(Self { matcher })
+    }
+
+    /// Whether the entry at `path`, as given by `path_from_root`, matches.
+    pub fn matches(&self, path: &str) -> bool {
+        match &self.matcher {
+            Matcher::Substring(s) => path.to_lowercase().contains(&s[..]),
+            Matcher::Regex { regex, name_only } => {
+                if *name_only {
+                    regex.is_match(path.rsplit('/').next().unwrap_or(path))
+                } else {
+                    regex.is_match(path)
+                }
+            }
+        }
+    }
+}
+
+fn search_node<'a>(
+    root: &Path,
+    node: &'a PathTreeNode,
+    query: &Query,
+    out: &mut Vec<&'a PathTreeNode>,
+) {
+    if query.matches(&path_from_root(root, node.path())) {
+        out.push(node);
+        return;
+    }
+    for (_, child) in node.sorted_children() {
+        search_node(root, child, query, out);
+    }
+}
+
+/// The path of `path` below `root`, as the names leading to it each with a `/` in front, like
+/// `/share/doc`.
+pub(crate) fn path_from_root(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .iter()
+        .map(|n| format!("/{}", n.to_string_lossy()))
+        .collect()
+}
+
+/// What a search found, for sending to the web client.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SearchResponse {
+    /// The first of the matches in path order, as given by `path_from_root`.
+    pub matches: Vec<String>,
+    /// How many matches there are, including any left out of `matches`.
+    pub num_matches: usize,
+    /// The size of all the matches.
+    pub num_bytes: u64,
+}
+
+impl PathTree {
+    /// The entries below the root matching `query`, in path order. Matches inside other matches
+    /// are left out, so the sizes of the results can be added up.
+    pub fn search(&self, query: &Query) -> Vec<&PathTreeNode> {
+        let mut out = vec![];
+        if let Some(root) = self.root() {
+            for (_, child) in root.sorted_children() {
+                search_node(root.path(), child, query, &mut out);
+            }
+        }
+        out
+    }
+
+    /// Searches for `query`, keeping the paths of at most `max_matches` of the matches.
+    pub fn search_response(&self, query: &Query, max_matches: usize) -> SearchResponse {
+        let root = self.root().map(|r| r.path().to_owned()).unwrap_or_default();
+        let found = self.search(query);
+        SearchResponse {
+            matches: found
+                .iter()
+                .take(max_matches)
+                .map(|n| path_from_root(&root, n.path()))
+                .collect(),
+            num_matches: found.len(),
+            num_bytes: found.iter().map(|n| n.num_bytes()).sum(),
+        }
+    }
+}
+
+#[test]
+fn glob_translates_to_anchored_regex() {
+    assert_eq!(glob_to_regex("core.*"), r"^core\.[^/]*$");
+    assert_eq!(glob_to_regex("/a/**/b?"), r"^/a/(.*/)?b[^/]$");
+    assert_eq!(glob_to_regex("/a/**"), r"^/a/.*$");
+    assert_eq!(glob_to_regex("[!ab]x["), r"^[^ab]x\[$");
+}
+
+#[test]
+fn glob_without_slash_matches_name() {
+    let query = Query::new(SearchMode::Glob, "core.[0-9]*").unwrap();
+    assert!(query.matches("/crash/core.1234"));
+    assert!(!query.matches("/core.d/x"));
+
+    let query = Query::new(SearchMode::Glob, "/*/core*").unwrap();
+    assert!(query.matches("/crash/core.1234"));
+    assert!(!query.matches("/a/b/core"));
+
+    let query = Query::new(SearchMode::Glob, "/a/**/b").unwrap();
+    assert!(query.matches("/a/b"));
+    assert!(query.matches("/a/x/y/b"));
+    assert!(!query.matches("/a/xb"));
+}
+
+#[test]
+fn substring_ignores_case_and_regex_searches_path() {
+    let query = Query::new(SearchMode::Substring, "CORE").unwrap();
+    assert!(query.matches("/var/Core.1"));
+
+    let query = Query::new(SearchMode::Regex, r"\.mkv$").unwrap();
+    assert!(query.matches("/films/a.mkv"));
+    assert!(!query.matches("/films/a.mkv.part"));
+
+    assert!(Query::new(SearchMode::Regex, "(").is_err());
+}
+
+#[test]
+fn search_leaves_out_matches_inside_matches() {
+    let tree = crate::test_tree(&[
+        ("/r", 0),
+        ("/r/logs", 0),
+        ("/r/logs/a.log", 1),
+        ("/r/b.log", 2),
+        ("/r/c", 3),
+        ("/r/arc", 4),
+    ]);
+    let query = Query::new(SearchMode::Substring, "log").unwrap();
+    let paths: Vec<_> = tree.search(&query).iter().map(|n| n.path()).collect();
+    assert_eq!(paths, [Path::new("/r/b.log"), Path::new("/r/logs")]);
+
+    // the path of the root itself isn't searched
+    let query = Query::new(SearchMode::Substring, "r").unwrap();
+    let paths: Vec<_> = tree.search(&query).iter().map(|n| n.path()).collect();
+    assert_eq!(paths, [Path::new("/r/arc")]);
+    let query = Query::new(SearchMode::Regex, "^/r").unwrap();
+    assert!(tree.search(&query).is_empty());
+}
+
+#[test]
+fn search_response_limits_matches() {
+    let tree = crate::test_tree(&[("/r", 0), ("/r/a.log", 1), ("/r/b", 0), ("/r/b/c.log", 2)]);
+    let query = Query::new(SearchMode::Glob, "*.log").unwrap();
+    assert_eq!(
+        tree.search_response(&query, 1),
+        SearchResponse {
+            matches: vec!["/a.log".into()],
+            num_matches: 2,
+            num_bytes: 3,
+        }
+    );
+}
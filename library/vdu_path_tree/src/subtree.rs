@@ -0,0 +1,119 @@
+// Copyright 2021 Remi Bernotavicius
+
+use crate::{NodeMetadata, PathTree, PathTreeNode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// An entry and its descendants down to some depth, for sending part of a tree at a time.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Subtree {
+    pub name: String,
+    pub num_bytes: u64,
+    /// How many entries are beneath it, including the ones left out.
+    pub num_descendants: usize,
+    pub metadata: NodeMetadata,
+    /// `None` below the requested depth, where only the totals above are sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<Subtree>>,
+}
+
+impl PathTreeNode {
+    /// This entry and `depth` levels of its descendants. Its name is the last component of its
+    /// path, or the whole path for the root.
+    pub fn subtree(&self, name: &str, depth: usize) -> Subtree {
+        Subtree {
+            name: name.into(),
+            num_bytes: self.num_bytes,
+            num_descendants: self.num_descendants,
+            metadata: self.metadata,
+            children: (depth > 0).then(|| {
+                self.sorted_children()
+                    .into_iter()
+                    .map(|(name, child)| child.subtree(name, depth - 1))
+                    .collect()
+            }),
+        }
+    }
+
+    /// False for directories whose children were left out when building the tree from a
+    /// `Subtree`; they still know how big they are.
+    pub fn is_loaded(&self) -> bool {
+        self.num_descendants == 0 || !self.children.is_empty()
+    }
+
+    fn from_subtree(parent: &Path, subtree: Subtree) -> Self {
+        let path = parent.join(&subtree.name);
+        let children: HashMap<_, _> = subtree
+            .children
+            .into_iter()
+            .flatten()
+            .map(|c| (c.name.clone(), Box::new(Self::from_subtree(&path, c))))
+            .collect();
+        Self {
+            path,
+            num_descendants: subtree.num_descendants,
+            num_bytes: subtree.num_bytes,
+            metadata: subtree.metadata,
+            children,
+        }
+    }
+}
+
+impl PathTree {
+    /// A tree holding `subtree`, which is the entry named in it inside the `parent` directory.
+    pub fn from_subtree(parent: &Path, subtree: Subtree) -> Self {
+        Self {
+            root: Some(Box::new(PathTreeNode::from_subtree(parent, subtree))),
+        }
+    }
+
+    /// Puts `subtree` in place of the entry with the same path, filling in what was left out of
+    /// it before.
+    pub fn load_subtree(&mut self, subtree: PathTree) {
+        if let Some(path) = subtree.root().map(|r| r.path().to_owned()) {
+            self.remove_path(&path);
+            self.merge(subtree);
+        }
+    }
+}
+
+#[test]
+fn subtree_stops_at_depth() {
+    let tree = crate::test_tree(&[("/r", 1), ("/r/a", 2), ("/r/a/b", 3), ("/r/c", 4)]);
+    let subtree = tree.root().unwrap().subtree("/r", 1);
+    assert_eq!(subtree.num_bytes, 10);
+    assert_eq!(subtree.num_descendants, 3);
+
+    let children = subtree.children.unwrap();
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0].name, "a");
+    assert_eq!(children[0].num_bytes, 5);
+    assert_eq!(children[0].num_descendants, 1);
+    assert_eq!(children[0].children, None);
+}
+
+#[test]
+fn partial_tree_fills_in_as_subtrees_load() {
+    let full = crate::test_tree(&[("/r", 1), ("/r/a", 2), ("/r/a/b", 3), ("/r/c", 4)]);
+    let root = full.root().unwrap();
+
+    let mut tree = PathTree::from_subtree(Path::new(""), root.subtree("/r", 1));
+    assert_eq!(tree.num_bytes(), 10);
+    assert_eq!(tree.size(), 4);
+    let a = tree.get(Path::new("/r/a")).unwrap();
+    assert!(!a.is_loaded());
+    assert_eq!(a.size(), 2);
+    assert!(tree.get(Path::new("/r/c")).unwrap().is_loaded());
+
+    let full_a = full.get(Path::new("/r/a")).unwrap();
+    tree.load_subtree(PathTree::from_subtree(
+        Path::new("/r"),
+        full_a.subtree("a", 1),
+    ));
+    assert!(tree.get(Path::new("/r/a")).unwrap().is_loaded());
+    assert_eq!(tree.get(Path::new("/r/a/b")).unwrap().num_bytes(), 3);
+    assert_eq!(tree.num_bytes(), 10);
+    assert_eq!(tree.size(), 4);
+    assert_eq!(tree.root().unwrap().own_num_bytes(), 1);
+}
@@ -0,0 +1,248 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Deleting the selected entry, when the server was started with `--allow-delete`. What would be
+//! removed is shown first, and nothing is deleted until that is confirmed. Unless the server was
+//! told to delete things for good, they are moved to the trash, and listed so they can be put
+//! back.
+
+use crate::vdu::Vdu;
+use crate::{loading, rescan, search, sidebar, window};
+use bytesize::ByteSize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use vdu_path_tree::{DeletePreview, TrashedEntry};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+fn element(id: &str) -> web_sys::HtmlElement {
+    let document = window().document().unwrap();
+    document
+        .get_element_by_id(id)
+        .unwrap()
+        .dyn_into::<web_sys::HtmlElement>()
+        .unwrap()
+}
+
+fn show_status(status: &str) {
+    element("delete-status").set_text_content(Some(status));
+}
+
+fn summary(preview: &DeletePreview) -> String {
+    let entries = match preview.num_entries {
+        1 => "1 entry".into(),
+        n => format!("{} entries", n),
+    };
+    let size = ByteSize::b(preview.num_bytes);
+    if preview.trash {
+        format!(
+            "move {} to the trash? {} ({}) will be moved, and can be put back from the list of \
+             what was moved to the trash.",
+            preview.path, entries, size
+        )
+    } else {
+        format!(
+            "delete {}? {} ({}) will be removed, which can't be undone.",
+            preview.path, entries, size
+        )
+    }
+}
+
+async fn restore(vdu: Rc<RefCell<Vdu>>, entry: TrashedEntry) {
+    show_status(&format!("restoring {}…", entry.path));
+    let status = match loading::restore(entry.id).await {
+        Ok(status) => status,
+        Err(error) => {
+            show_status(&format!("couldn't restore {}: {}", entry.path, error));
+            return;
+        }
+    };
+    show_trashed(&vdu, status.trashed);
+    match rescan::reload_tree(&vdu).await {
+        Ok(()) => show_status(&format!("restored {}", entry.path)),
+        Err(error) => show_status(&format!("couldn't load the tree again: {}", error)),
+    }
+}
+
+/// Lists what was moved to the trash, most recent first, each with a button to put it back.
+fn show_trashed(vdu: &Rc<RefCell<Vdu>>, trashed: Vec<TrashedEntry>) {
+    let document = window().document().unwrap();
+    element("trashed").set_hidden(trashed.is_empty());
+    element("trashed-summary")
+        .set_text_content(Some(&format!("moved to trash ({})", trashed.len())));
+    let list = element("trashed-list");
+    list.set_text_content(None);
+    for entry in trashed.into_iter().rev() {
+        let item = document.create_element("li").unwrap();
+        let text = format!("{} ({}) ", entry.path, ByteSize::b(entry.num_bytes));
+        item.append_with_str_1(&text).unwrap();
+
+        let undo = document.create_element("button").unwrap();
+        undo.set_text_content(Some("undo"));
+        let vdu = vdu.clone();
+        let mut entry = Some(entry);
+        let closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
+            // the list is made again once it has been restored
+            if let Some(entry) = entry.take() {
+                wasm_bindgen_futures::spawn_local(restore(vdu.clone(), entry));
+            }
+        }) as Box<dyn FnMut(_)>);
+        undo.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())
+            .unwrap();
+        closure.forget();
+        item.append_child(&undo).unwrap();
+        list.append_child(&item).unwrap();
+    }
+}
+
+/// Shows what deleting would remove and waits for the delete or cancel button to be clicked.
+async fn confirm(preview: &DeletePreview) -> bool {
+    element("delete-summary").set_text_content(Some(&summary(preview)));
+    let document = window().document().unwrap();
+    let list = element("delete-largest");
+    list.set_text_content(None);
+    for (path, num_bytes) in &preview.largest {
+        let item = document.create_element("li").unwrap();
+        item.set_text_content(Some(&format!("{} ({})", path, ByteSize::b(*num_bytes))));
+        list.append_child(&item).unwrap();
+    }
+
+    let dialog = element("delete-dialog");
+    let delete = element("delete-confirm");
+    let cancel = element("delete-cancel");
+    let label = if preview.trash {
+        "move to trash"
+    } else {
+        "delete"
+    };
+    delete.set_text_content(Some(label));
+    dialog.set_hidden(false);
+    // so enter doesn't delete anything by accident
+    cancel.focus().unwrap();
+
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        delete.set_onclick(Some(&resolve.bind1(&JsValue::NULL, &JsValue::TRUE)));
+        cancel.set_onclick(Some(&resolve.bind1(&JsValue::NULL, &JsValue::FALSE)));
+    });
+    let confirmed = JsFuture::from(promise).await.ok().and_then(|v| v.as_bool());
+    delete.set_onclick(None);
+    cancel.set_onclick(None);
+    dialog.set_hidden(true);
+    confirmed == Some(true)
+}
+
+async fn delete_selected(vdu: Rc<RefCell<Vdu>>) {
+    let names = match vdu.borrow().selected_names() {
+        Some(names) => names,
+        None => {
+            show_status("select something to delete first");
+            return;
+        }
+    };
+    let path: String = names.iter().map(|n| format!("/{}", n)).collect();
+
+    let preview = match loading::delete_preview(&path).await {
+        Ok(preview) => preview,
+        Err(error) => {
+            show_status(&format!("can't delete {}: {}", path, error));
+            return;
+        }
+    };
+    show_status("");
+    if !confirm(&preview).await {
+        return;
+    }
+
+    show_status(&format!("deleting {}…", preview.path));
+    match loading::delete(&path, &preview.path).await {
+        Ok(deleted) => {
+            vdu.borrow_mut().remove_entry(&names);
+            sidebar::update(&vdu.borrow());
+            search::rerun();
+            let done = if deleted.trash {
+                "moved to the trash"
+            } else {
+                "deleted"
+            };
+            show_status(&format!(
+                "{} {} ({})",
+                done,
+                deleted.path,
+                ByteSize::b(deleted.num_bytes)
+            ));
+            if deleted.trash {
+                match loading::delete_status().await {
+                    Ok(status) => show_trashed(&vdu, status.trashed),
+                    Err(error) => crate::log(&format!("couldn't list the trash: {}", error)),
+                }
+            }
+        }
+        Err(error) => show_status(&format!("couldn't delete {}: {}", preview.path, error)),
+    }
+}
+
+/// Asks whether to delete the selected entry, then deletes it. Does nothing unless the server
+/// allows deleting, or while already asking.
+pub fn ask_to_delete_selected(vdu: &Rc<RefCell<Vdu>>) {
+    if element("delete").hidden() || !element("delete-dialog").hidden() {
+        return;
+    }
+    wasm_bindgen_futures::spawn_local(delete_selected(vdu.clone()));
+}
+
+/// Shows the delete button if the server allows deleting.
+pub fn set_up_delete(vdu: Rc<RefCell<Vdu>>) {
+    // keys pressed in the dialog are kept from the treemap, and escape cancels
+    let closure = Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
+        if event.key() == "Escape" {
+            element("delete-cancel").click();
+        }
+        event.stop_propagation();
+    }) as Box<dyn FnMut(_)>);
+    element("delete-dialog")
+        .add_event_listener_with_callback("keydown", closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+
+    wasm_bindgen_futures::spawn_local(async move {
+        let status = match loading::delete_status().await {
+            Ok(status) if status.allowed => status,
+            Ok(_) => return,
+            Err(error) => {
+                crate::log(&format!(
+                    "couldn't find out if deleting is allowed: {}",
+                    error
+                ));
+                return;
+            }
+        };
+        show_trashed(&vdu, status.trashed);
+
+        let button = element("delete");
+        button.set_hidden(false);
+        let closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
+            ask_to_delete_selected(&vdu);
+        }) as Box<dyn FnMut(_)>);
+        button.set_onclick(Some(closure.as_ref().unchecked_ref()));
+        closure.forget();
+    });
+}
+
+#[test]
+fn summary_counts_entries() {
+    let mut preview = DeletePreview {
+        path: "/home/me/big".into(),
+        num_bytes: 2048,
+        num_entries: 1,
+        largest: vec![],
+        trash: false,
+    };
+    assert_eq!(
+        summary(&preview),
+        "delete /home/me/big? 1 entry (2.0 KiB) will be removed, which can't be undone."
+    );
+    preview.num_entries = 3;
+    assert!(summary(&preview).contains("3 entries (2.0 KiB)"));
+    preview.trash = true;
+    assert!(summary(&preview).starts_with("move /home/me/big to the trash? 3 entries"));
+}
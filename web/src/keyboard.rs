@@ -0,0 +1,101 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Driving the treemap from the keyboard: arrows move the selection between the rectangles in
+//! the view, enter goes into the selected directory, backspace or escape goes back up, `/` jumps
+//! to the search box, `c` copies the selected path and delete asks to delete the selected entry,
+//! if the server allows it.
+
+use crate::vdu::Vdu;
+use crate::{delete, navigation, window};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+fn copy_to_clipboard(text: &str) -> Result<(), JsValue> {
+    // navigator.clipboard is only there in secure contexts, so it is looked up at runtime
+    let navigator = window().navigator();
+    let clipboard = js_sys::Reflect::get(&navigator, &"clipboard".into())?;
+    let write_text =
+        js_sys::Reflect::get(&clipboard, &"writeText".into())?.dyn_into::<js_sys::Function>()?;
+    write_text.call1(&clipboard, &text.into())?;
+    Ok(())
+}
+
+fn is_text_field(target: Option<web_sys::EventTarget>) -> Option<web_sys::HtmlElement> {
+    let element = target?.dyn_into::<web_sys::HtmlElement>().ok()?;
+    matches!(&element.tag_name()[..], "INPUT" | "SELECT" | "TEXTAREA").then_some(element)
+}
+
+fn on_key_down(vdu: &Rc<RefCell<Vdu>>, event: &web_sys::KeyboardEvent) {
+    if event.ctrl_key() || event.meta_key() || event.alt_key() {
+        return;
+    }
+
+    if let Some(field) = is_text_field(event.target()) {
+        // escape leaves the search box, so the keys work on the treemap again
+        if event.key() == "Escape" {
+            field.blur().unwrap();
+        }
+        return;
+    }
+
+    let direction = match &event.key()[..] {
+        "ArrowLeft" => Some((-1.0, 0.0)),
+        "ArrowRight" => Some((1.0, 0.0)),
+        "ArrowUp" => Some((0.0, -1.0)),
+        "ArrowDown" => Some((0.0, 1.0)),
+        _ => None,
+    };
+
+    match (&event.key()[..], direction) {
+        (_, Some(direction)) => vdu.borrow_mut().move_selection(direction),
+        ("Enter", _) => {
+            let selected = {
+                let vdu = vdu.borrow();
+                vdu.selected_names()
+                    .map(|names| (names, vdu.selected_is_directory()))
+            };
+            match selected {
+                Some((names, true)) => navigation::go_to(&names),
+                Some((_, false)) => {}
+                None => vdu.borrow_mut().select_largest(),
+            }
+        }
+        ("Backspace", _) | ("Escape", _) => {
+            let view = vdu.borrow().view().to_vec();
+            if let Some((_, parent)) = view.split_last() {
+                navigation::go_to(parent);
+            }
+        }
+        ("/", _) => {
+            let document = window().document().unwrap();
+            let search = document
+                .get_element_by_id("search")
+                .unwrap()
+                .dyn_into::<web_sys::HtmlElement>()
+                .unwrap();
+            search.focus().unwrap();
+        }
+        ("c", _) => {
+            if let Some(path) = vdu.borrow().selected_path() {
+                if let Err(error) = copy_to_clipboard(&path) {
+                    crate::log(&format!("couldn't copy {}: {:?}", path, error));
+                }
+            }
+        }
+        ("Delete", _) => delete::ask_to_delete_selected(vdu),
+        _ => return,
+    }
+    event.prevent_default();
+}
+
+pub fn set_up_keyboard(vdu: Rc<RefCell<Vdu>>) {
+    let closure = Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
+        on_key_down(&vdu, &event);
+    }) as Box<dyn FnMut(_)>);
+    window()
+        .add_event_listener_with_callback("keydown", closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+}
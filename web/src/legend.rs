@@ -0,0 +1,25 @@
+// copyright 2021 Remi Bernotavicius
+
+//! The key to what the colors in the treemap mean.
+
+use crate::window;
+
+pub fn show(entries: &[(String, &str)]) {
+    let document = window().document().unwrap();
+    let legend = document.get_element_by_id("legend").unwrap();
+    legend.set_text_content(None);
+
+    for (name, color) in entries {
+        let swatch = document.create_element("span").unwrap();
+        swatch.set_class_name("swatch");
+        swatch
+            .set_attribute("style", &format!("background: {}", color))
+            .unwrap();
+
+        let entry = document.create_element("span").unwrap();
+        entry.set_class_name("legend-entry");
+        entry.append_child(&swatch).unwrap();
+        entry.append_with_str_1(name).unwrap();
+        legend.append_child(&entry).unwrap();
+    }
+}
@@ -1,13 +1,21 @@
 // copyright 2021 Remi Bernotavicius
 use wasm_bindgen::prelude::*;
-use web_sys::{Request, RequestInit, RequestMode, Response};
 use vdu_path_tree::PathTree;
-use wasm_bindgen_futures::JsFuture;
 use wasm_bindgen::JsCast;
-use vdu::Vdu;
+use vdu::{ColorScheme, Layout, Vdu};
 use std::cell::RefCell;
 use std::rc::Rc;
 
+mod delete;
+mod keyboard;
+mod legend;
+mod loading;
+mod navigation;
+mod rescan;
+mod search;
+mod sidebar;
+mod tooltip;
+mod touch;
 mod vdu;
 
 #[wasm_bindgen]
@@ -41,27 +49,6 @@ fn get_drawing_context(canvas: &web_sys::HtmlCanvasElement) -> web_sys::CanvasRe
         .unwrap()
 }
 
-async fn load_path_tree() -> Result<PathTree, JsValue> {
-    let mut opts = RequestInit::new();
-    opts.method("GET");
-    opts.mode(RequestMode::Cors);
-
-    let request = Request::new_with_str_and_init("./tree", &opts)?;
-
-    request
-        .headers()
-        .set("Accept", "application/octet-stream")?;
-
-    let window = window();
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
-
-    let resp: Response = resp_value.dyn_into().unwrap();
-    let value = JsFuture::from(resp.array_buffer()?).await?;
-    let array = js_sys::Uint8Array::new(&value);
-    let buffer = array.to_vec();
-    Ok(bincode::deserialize(&buffer[..]).unwrap())
-}
-
 fn request_animation_frame(f: &Closure<dyn FnMut()>) {
     window()
         .request_animation_frame(f.as_ref().unchecked_ref())
@@ -73,7 +60,9 @@ fn set_up_rendering(vdu: Rc<RefCell<Vdu>>) {
     let g = f.clone();
 
     *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+        // This does nothing unless something has changed since the last frame.
         vdu.borrow_mut().render();
+        loading::load_unloaded(&vdu);
 
         // Schedule ourself for another requestAnimationFrame callback.
         request_animation_frame(f.borrow().as_ref().unwrap());
@@ -82,52 +71,147 @@ fn set_up_rendering(vdu: Rc<RefCell<Vdu>>) {
     request_animation_frame(g.borrow().as_ref().unwrap());
 }
 
-fn set_canvas_to_window_size(canvas: &web_sys::HtmlCanvasElement) {
+/// Fills the window below the toolbar with the canvas, giving it a pixel for every pixel of the
+/// screen so it isn't blurry on high density displays.
+fn fit_canvas_to_window(vdu: &RefCell<Vdu>) {
+    let canvas = canvas();
     let document = window().document().unwrap();
     let elem = document.document_element().unwrap();
-    let width = elem.client_width();
-    let height = elem.client_height();
+    let sidebar = document
+        .get_element_by_id("sidebar")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlElement>()
+        .unwrap();
+    let width = (elem.client_width() - sidebar.offset_width() - 20).max(0) as f64;
+    let height = (elem.client_height() - canvas.offset_top() - 20).max(0) as f64;
+    sidebar
+        .style()
+        .set_property("height", &format!("{}px", height))
+        .unwrap();
+    let pixel_ratio = window().device_pixel_ratio();
+
+    canvas.set_width((width * pixel_ratio).round() as u32);
+    canvas.set_height((height * pixel_ratio).round() as u32);
+    let style = canvas.style();
+    style.set_property("width", &format!("{}px", width)).unwrap();
+    style.set_property("height", &format!("{}px", height)).unwrap();
 
-    canvas.set_width(width as u32 - 20);
-    canvas.set_height(height as u32 - 20);
+    // mouse events and the layout are in CSS pixels
+    vdu.borrow_mut().on_resize(width, height, pixel_ratio);
 }
 
 fn set_up_input(vdu: Rc<RefCell<Vdu>>) {
     let canvas = canvas();
+    let vdu_for_click = vdu.clone();
+
+    let vdu_for_leave = vdu.clone();
 
     let closure = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
-        vdu
-            .borrow_mut()
-            .on_mouse_move(event.offset_x() as f64, event.offset_y() as f64);
+        let mut vdu = vdu.borrow_mut();
+        vdu.on_mouse_move(event.offset_x() as f64, event.offset_y() as f64);
+        match vdu.hover_details() {
+            Some(lines) => tooltip::show(&lines, event.client_x(), event.client_y()),
+            None => tooltip::hide(),
+        }
+        sidebar::highlight_row(vdu.hovered_child().as_deref());
     }) as Box<dyn FnMut(_)>);
 
     canvas
         .add_event_listener_with_callback("mousemove", closure.as_ref().unchecked_ref())
         .unwrap();
     closure.forget();
+
+    let closure = Closure::wrap(Box::new(move |_: web_sys::MouseEvent| {
+        vdu_for_leave.borrow_mut().on_mouse_leave();
+        tooltip::hide();
+        sidebar::highlight_row(None);
+    }) as Box<dyn FnMut(_)>);
+
+    canvas
+        .add_event_listener_with_callback("mouseleave", closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+
+    let closure = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
+        let (x, y) = (event.offset_x() as f64, event.offset_y() as f64);
+        let new_view = vdu_for_click.borrow().on_click(x, y);
+        match new_view {
+            Some(new_view) => navigation::go_to(&new_view),
+            // clicking a file in the view selects it, like for deleting it
+            None => vdu_for_click.borrow_mut().select_at(x, y),
+        }
+    }) as Box<dyn FnMut(_)>);
+
+    canvas
+        .add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+}
+
+fn on_select_change(id: &str, mut f: impl FnMut(String) + 'static) {
+    let document = window().document().unwrap();
+    let select = document
+        .get_element_by_id(id)
+        .unwrap()
+        .dyn_into::<web_sys::HtmlSelectElement>()
+        .unwrap();
+
+    let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
+        let select = event
+            .target()
+            .unwrap()
+            .dyn_into::<web_sys::HtmlSelectElement>()
+            .unwrap();
+        f(select.value())
+    }) as Box<dyn FnMut(_)>);
+
+    select
+        .add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+}
+
+fn set_up_controls(vdu: Rc<RefCell<Vdu>>) {
+    let vdu_for_layout = vdu.clone();
+    on_select_change("layout", move |value| match value.parse::<Layout>() {
+        Ok(layout) => vdu_for_layout.borrow_mut().set_layout(layout),
+        Err(error) => console_log!("{}", error),
+    });
+
+    legend::show(&vdu.borrow().legend());
+    on_select_change("color-scheme", move |value| match value.parse::<ColorScheme>() {
+        Ok(scheme) => {
+            vdu.borrow_mut().set_color_scheme(scheme);
+            legend::show(&vdu.borrow().legend());
+        }
+        Err(error) => console_log!("{}", error),
+    });
 }
 
 fn display_path_tree(tree: PathTree) -> Result<(), JsValue> {
     console_log!("path tree; {} nodes {} bytes", tree.size(), tree.num_bytes());
 
-    set_canvas_to_window_size(&canvas());
+    let drawing_context = get_drawing_context(&canvas());
+    let vdu = Rc::new(RefCell::new(Vdu::new(drawing_context, tree)));
+    fit_canvas_to_window(&vdu);
 
+    let vdu_for_resize = vdu.clone();
     let closure = Closure::wrap(Box::new(move |_: JsValue| {
-        let canvas = canvas();
-        set_canvas_to_window_size(&canvas);
+        fit_canvas_to_window(&vdu_for_resize);
     }) as Box<dyn FnMut(_)>);
     window().add_event_listener_with_callback("resize", closure.as_ref().unchecked_ref())?;
     closure.forget();
 
-    let drawing_context = get_drawing_context(&canvas());
-    let vdu = Rc::new(RefCell::new(Vdu::new(
-        drawing_context,
-        canvas(),
-        tree,
-    )));
-
     set_up_rendering(vdu.clone());
     set_up_input(vdu.clone());
+    set_up_controls(vdu.clone());
+    sidebar::set_up_sidebar(vdu.clone());
+    search::set_up_search(vdu.clone());
+    keyboard::set_up_keyboard(vdu.clone());
+    touch::set_up_touch(vdu.clone());
+    navigation::set_up_navigation(vdu.clone());
+    rescan::set_up_rescan(vdu.clone());
+    delete::set_up_delete(vdu.clone());
 
     Ok(())
 }
@@ -137,8 +221,17 @@ pub async fn start() -> Result<(), JsValue> {
     console_error_panic_hook::set_once();
     console_log!("VDU loading");
 
-    let tree = load_path_tree().await?;
-    display_path_tree(tree)?;
-
-    Ok(())
+    loop {
+        match loading::load_path_tree().await {
+            Ok(tree) => {
+                loading::finish();
+                return display_path_tree(tree);
+            }
+            Err(error) => {
+                console_log!("failed to load tree: {}", error);
+                loading::show_error(&error);
+                loading::wait_for_retry().await;
+            }
+        }
+    }
 }
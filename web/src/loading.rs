@@ -0,0 +1,351 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Downloading the tree from the server, showing progress and any errors on the page. Only the
+//! top of the tree is downloaded up front, and directories are filled in as they are zoomed into.
+
+use crate::vdu::Vdu;
+use crate::{navigation, sidebar, window};
+use bytesize::ByteSize;
+use serde::de::DeserializeOwned;
+use std::cell::RefCell;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+use vdu_path_tree::{
+    DeletePreview, DeleteStatus, PathTree, RescanStatus, SearchResponse, Subtree,
+    API_REQUEST_HEADER, CONFIRM_DELETE_HEADER, DECODED_LENGTH_HEADER, FORMAT_VERSION,
+    FORMAT_VERSION_HEADER,
+};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ReadableStreamDefaultReader, Request, RequestInit, RequestMode, Response};
+
+/// How many levels of the tree are loaded before anything is drawn.
+const INITIAL_DEPTH: usize = 3;
+
+/// How many levels are loaded below a directory once it needs its contents.
+const SUBTREE_DEPTH: usize = 2;
+
+pub enum LoadError {
+    /// The request couldn't be made or the connection was lost.
+    Network(String),
+    Http {
+        status: u16,
+        status_text: String,
+        /// What the server said went wrong, if anything.
+        message: Option<String>,
+    },
+    /// The server sends the tree in a different format, or didn't say which.
+    Version {
+        server: Option<String>,
+    },
+    Decode(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(error) => write!(f, "couldn't reach the server: {}", error),
+            Self::Http {
+                status,
+                status_text,
+                message,
+            } => {
+                write!(f, "the server responded with {} {}", status, status_text)?;
+                match message {
+                    Some(message) => write!(f, ": {}", message),
+                    None => Ok(()),
+                }
+            }
+            Self::Version { server: Some(v) } => write!(
+                f,
+                "the server sends version {} of the tree format, but this page understands \
+                 version {}; try reloading the page",
+                v, FORMAT_VERSION
+            ),
+            Self::Version { server: None } => write!(
+                f,
+                "the server didn't say which version of the tree format it sends"
+            ),
+            Self::Decode(error) => write!(f, "the tree couldn't be read: {}", error),
+        }
+    }
+}
+
+impl From<JsValue> for LoadError {
+    fn from(value: JsValue) -> Self {
+        let message = match value.dyn_ref::<js_sys::Error>() {
+            Some(error) => error.message().into(),
+            None => value.as_string().unwrap_or_else(|| format!("{:?}", value)),
+        };
+        Self::Network(message)
+    }
+}
+
+fn progress_text(received: u64, total: Option<u64>) -> String {
+    match total {
+        Some(total) if total > 0 => format!(
+            "loading… {} of {} ({:.0}%)",
+            ByteSize::b(received),
+            ByteSize::b(total),
+            received as f64 / total as f64 * 100.0
+        ),
+        _ => format!("loading… {}", ByteSize::b(received)),
+    }
+}
+
+fn element(id: &str) -> web_sys::HtmlElement {
+    let document = window().document().unwrap();
+    document
+        .get_element_by_id(id)
+        .unwrap()
+        .dyn_into::<web_sys::HtmlElement>()
+        .unwrap()
+}
+
+fn show_progress(received: u64, total: Option<u64>) {
+    element("loading-status").set_text_content(Some(&progress_text(received, total)));
+
+    let progress = element("loading-progress");
+    progress.set_hidden(false);
+    match total {
+        Some(total) => {
+            progress.set_attribute("max", &total.to_string()).unwrap();
+            progress
+                .set_attribute("value", &received.to_string())
+                .unwrap();
+        }
+        // with no value the bar shows something is happening without saying how much
+        None => progress.remove_attribute("value").unwrap(),
+    }
+}
+
+pub fn show_error(error: &LoadError) {
+    element("loading-status").set_text_content(Some(&error.to_string()));
+    element("loading-progress").set_hidden(true);
+    element("retry").set_hidden(false);
+}
+
+/// Takes the loading indicator off the page once the tree is shown.
+pub fn finish() {
+    element("loading").set_hidden(true);
+}
+
+/// Waits for the retry button to be clicked.
+pub async fn wait_for_retry() {
+    let retry = element("retry");
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        retry.set_onclick(Some(resolve.unchecked_ref()));
+    });
+    let _ = JsFuture::from(promise).await;
+    retry.set_onclick(None);
+    retry.set_hidden(true);
+}
+
+async fn read_body(response: &Response, show: bool) -> Result<Vec<u8>, JsValue> {
+    // compressed bodies are decompressed before they get here, so their Content-Length doesn't
+    // say how much there is to read
+    let headers = response.headers();
+    let length = match headers.get(DECODED_LENGTH_HEADER)? {
+        Some(length) => Some(length),
+        None if headers.get("Content-Encoding")?.is_none() => headers.get("Content-Length")?,
+        None => None,
+    };
+    let total = length.and_then(|l| l.parse::<u64>().ok());
+
+    let body = match response.body() {
+        Some(body) => body,
+        None => {
+            let value = JsFuture::from(response.array_buffer()?).await?;
+            return Ok(js_sys::Uint8Array::new(&value).to_vec());
+        }
+    };
+
+    let reader = body
+        .get_reader()
+        .dyn_into::<ReadableStreamDefaultReader>()?;
+    let mut data = Vec::with_capacity(total.unwrap_or(0) as usize);
+    loop {
+        let result = JsFuture::from(reader.read()).await?;
+        let done = js_sys::Reflect::get(&result, &"done".into())?;
+        if done.as_bool().unwrap_or(true) {
+            break;
+        }
+
+        let chunk = js_sys::Uint8Array::new(&js_sys::Reflect::get(&result, &"value".into())?);
+        let start = data.len();
+        data.resize(start + chunk.length() as usize, 0);
+        chunk.copy_to(&mut data[start..]);
+        if show {
+            show_progress(data.len() as u64, total);
+        }
+    }
+    Ok(data)
+}
+
+/// Makes a `method` request for `url` to the server's API, with `headers` added, and decodes the
+/// JSON it responds with, showing how much has been downloaded if `show` is set.
+async fn fetch_json_with_headers<T: DeserializeOwned>(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    show: bool,
+) -> Result<T, LoadError> {
+    let mut opts = RequestInit::new();
+    opts.method(method);
+    opts.mode(RequestMode::Cors);
+
+    let request = Request::new_with_str_and_init(url, &opts)?;
+
+    request.headers().set("Accept", "application/json")?;
+    if method != "GET" {
+        request.headers().set(API_REQUEST_HEADER, "1")?;
+    }
+    for &(name, value) in headers {
+        request.headers().set(name, value)?;
+    }
+
+    let resp_value = JsFuture::from(window().fetch_with_request(&request)).await?;
+    let resp: Response = resp_value.dyn_into().unwrap();
+    if !resp.ok() {
+        // the API explains errors in plain text
+        let text = JsFuture::from(resp.text()?).await?.as_string();
+        return Err(LoadError::Http {
+            status: resp.status(),
+            status_text: resp.status_text(),
+            message: text.filter(|t| !t.trim().is_empty()),
+        });
+    }
+
+    let version = resp.headers().get(FORMAT_VERSION_HEADER)?;
+    if version.as_deref() != Some(&FORMAT_VERSION.to_string()[..]) {
+        return Err(LoadError::Version { server: version });
+    }
+
+    let buffer = read_body(&resp, show).await?;
+    serde_json::from_slice(&buffer[..]).map_err(|e| LoadError::Decode(e.to_string()))
+}
+
+async fn fetch_json<T: DeserializeOwned>(
+    method: &str,
+    url: &str,
+    show: bool,
+) -> Result<T, LoadError> {
+    fetch_json_with_headers(method, url, &[], show).await
+}
+
+/// `path`, which is in the form of the paths of cells, as it goes on the end of an API URL.
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .skip(1)
+        .map(|n| format!("/{}", js_sys::encode_uri_component(n)))
+        .collect()
+}
+
+/// The API's URL for the entry at `path`.
+fn subtree_url(path: &str, depth: usize) -> String {
+    format!("./api/tree{}?depth={}", encode_path(path), depth)
+}
+
+async fn fetch_path_tree(show: bool) -> Result<PathTree, LoadError> {
+    let subtree = fetch_json("GET", &subtree_url("", INITIAL_DEPTH), show).await?;
+    Ok(PathTree::from_subtree(Path::new(""), subtree))
+}
+
+/// Loads the top levels of the tree; the rest is loaded as it comes into view.
+pub async fn load_path_tree() -> Result<PathTree, LoadError> {
+    show_progress(0, None);
+    fetch_path_tree(true).await
+}
+
+/// Loads the top levels of the tree again after it was scanned again, without showing progress.
+pub async fn reload_path_tree() -> Result<PathTree, LoadError> {
+    fetch_path_tree(false).await
+}
+
+async fn load_subtree(vdu: Rc<RefCell<Vdu>>, path: String) {
+    let generation = vdu.borrow().tree_generation();
+    let response = fetch_json::<Subtree>("GET", &subtree_url(&path, SUBTREE_DEPTH), false).await;
+    // what was asked for is from a tree that has since been replaced
+    if vdu.borrow().tree_generation() != generation {
+        return;
+    }
+    match response {
+        Ok(subtree) => {
+            let is_view = vdu.borrow().view_path() == path;
+            vdu.borrow_mut().load_subtree(&path, subtree);
+            if is_view {
+                sidebar::update(&vdu.borrow());
+            }
+            navigation::follow_hash(&vdu);
+        }
+        Err(error) => {
+            crate::log(&format!("failed to load {}: {}", path, error));
+            vdu.borrow_mut().subtree_failed(&path);
+        }
+    }
+}
+
+/// Starts loading the directories that have come into view without their contents.
+pub fn load_unloaded(vdu: &Rc<RefCell<Vdu>>) {
+    let unloaded = vdu.borrow_mut().take_unloaded();
+    for path in unloaded {
+        wasm_bindgen_futures::spawn_local(load_subtree(vdu.clone(), path));
+    }
+}
+
+/// Searches the whole tree on the server, including the parts not loaded yet.
+pub async fn search(mode: &str, pattern: &str) -> Result<SearchResponse, LoadError> {
+    let url = format!(
+        "./api/search?mode={}&pattern={}",
+        js_sys::encode_uri_component(mode),
+        js_sys::encode_uri_component(pattern)
+    );
+    fetch_json("GET", &url, false).await
+}
+
+/// Asks the server to scan the entry at `path` again, or the whole tree if it was combined from
+/// several paths and `path` is above them.
+pub async fn start_rescan(path: &str) -> Result<RescanStatus, LoadError> {
+    fetch_json("POST", &format!("./api/rescan{}", encode_path(path)), false).await
+}
+
+pub async fn rescan_status() -> Result<RescanStatus, LoadError> {
+    fetch_json("GET", "./api/rescan", false).await
+}
+
+pub async fn delete_status() -> Result<DeleteStatus, LoadError> {
+    fetch_json("GET", "./api/delete", false).await
+}
+
+/// Asks the server what deleting the entry at `path` would remove, without deleting anything.
+pub async fn delete_preview(path: &str) -> Result<DeletePreview, LoadError> {
+    fetch_json("GET", &format!("./api/delete{}", encode_path(path)), false).await
+}
+
+/// Moves what the server moved to the trash with the given ID back out of it.
+pub async fn restore(id: u64) -> Result<DeleteStatus, LoadError> {
+    fetch_json("POST", &format!("./api/restore/{}", id), false).await
+}
+
+/// Deletes the entry at `path`, which the server says is at `disk_path`.
+pub async fn delete(path: &str, disk_path: &str) -> Result<DeletePreview, LoadError> {
+    let confirm: String = js_sys::encode_uri_component(disk_path).into();
+    fetch_json_with_headers(
+        "POST",
+        &format!("./api/delete{}", encode_path(path)),
+        &[(CONFIRM_DELETE_HEADER, &confirm)],
+        false,
+    )
+    .await
+}
+
+#[test]
+fn progress_text_shows_share_when_total_known() {
+    assert_eq!(
+        progress_text(512, Some(2048)),
+        "loading… 512 B of 2.0 KiB (25%)"
+    );
+    assert_eq!(progress_text(2048, None), "loading… 2.0 KiB");
+}
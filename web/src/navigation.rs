@@ -0,0 +1,86 @@
+// copyright 2021 Remi Bernotavicius
+
+//! The directory being viewed is kept in the location hash (e.g. `#/usr/share`), so the browser's
+//! back and forward buttons move between the directories that were zoomed into.
+
+use crate::sidebar;
+use crate::vdu::Vdu;
+use crate::window;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+fn view_hash(names: &[String]) -> String {
+    let encoded: Vec<String> = names
+        .iter()
+        .map(|n| js_sys::encode_uri_component(n).into())
+        .collect();
+    format!("#/{}", encoded.join("/"))
+}
+
+fn view_from_hash() -> Vec<String> {
+    let hash = window().location().hash().unwrap_or_default();
+    hash.trim_start_matches('#')
+        .split('/')
+        .filter(|n| !n.is_empty())
+        .filter_map(|n| js_sys::decode_uri_component(n).ok())
+        .map(String::from)
+        .collect()
+}
+
+/// Navigates to the given directory, adding an entry to the browser history.
+pub fn go_to(names: &[String]) {
+    window().location().set_hash(&view_hash(names)).unwrap();
+}
+
+fn update_breadcrumbs(vdu: &Vdu) {
+    let document = window().document().unwrap();
+    let nav = document.get_element_by_id("breadcrumbs").unwrap();
+    nav.set_text_content(None);
+
+    let view = vdu.view();
+    for i in 0..=view.len() {
+        if i > 0 {
+            nav.append_with_str_1(" / ").unwrap();
+        }
+        let name = if i == 0 {
+            vdu.root_name()
+        } else {
+            view[i - 1].clone()
+        };
+        let link = document.create_element("a").unwrap();
+        link.set_attribute("href", &view_hash(&view[..i])).unwrap();
+        link.set_text_content(Some(&name));
+        nav.append_child(&link).unwrap();
+    }
+}
+
+/// Shows the directory in the location hash, or the deepest one along the way that exists.
+pub fn show_view_from_hash(vdu: &RefCell<Vdu>) {
+    vdu.borrow_mut().set_view(view_from_hash());
+    update_breadcrumbs(&vdu.borrow());
+    sidebar::update(&vdu.borrow());
+}
+
+/// Goes on towards the directory in the location hash, if it is deeper than the view because the
+/// directories on the way to it hadn't been loaded yet.
+pub fn follow_hash(vdu: &RefCell<Vdu>) {
+    let names = view_from_hash();
+    let view = vdu.borrow().view().to_vec();
+    if names.len() > view.len() && names.starts_with(&view) {
+        show_view_from_hash(vdu);
+    }
+}
+
+pub fn set_up_navigation(vdu: Rc<RefCell<Vdu>>) {
+    show_view_from_hash(&vdu);
+
+    let closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
+        show_view_from_hash(&vdu);
+    }) as Box<dyn FnMut(_)>);
+    window()
+        .add_event_listener_with_callback("hashchange", closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+}
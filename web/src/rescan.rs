@@ -0,0 +1,134 @@
+// copyright 2021 Remi Bernotavicius
+
+//! The rescan button, which has the server scan the directory being viewed again and swaps in the
+//! new tree once it is done, so what was cleaned up shows without restarting the server.
+
+use crate::vdu::Vdu;
+use crate::{loading, navigation, search, window};
+use std::cell::RefCell;
+use std::rc::Rc;
+use vdu_path_tree::RescanStatus;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+/// How long to wait between asking the server how the scan is going.
+const POLL_INTERVAL_MS: i32 = 500;
+
+fn button() -> web_sys::HtmlButtonElement {
+    let document = window().document().unwrap();
+    document
+        .get_element_by_id("rescan")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlButtonElement>()
+        .unwrap()
+}
+
+fn show_status(status: &str) {
+    let document = window().document().unwrap();
+    let element = document.get_element_by_id("rescan-status").unwrap();
+    element.set_text_content(Some(status));
+}
+
+fn status_text(status: &RescanStatus) -> String {
+    match (&status.scanning, &status.error) {
+        (Some(path), _) => format!("scanning {}… {} entries", path, status.entries),
+        (None, Some(error)) => format!("scan failed: {}", error),
+        (None, None) => String::new(),
+    }
+}
+
+async fn sleep(ms: i32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        window()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+            .unwrap();
+    });
+    let _ = JsFuture::from(promise).await;
+}
+
+/// Waits for the scan the server is running to finish, showing how far it has got.
+async fn wait_for_scan(mut status: RescanStatus) -> Result<RescanStatus, loading::LoadError> {
+    while status.scanning.is_some() {
+        show_status(&status_text(&status));
+        sleep(POLL_INTERVAL_MS).await;
+        status = loading::rescan_status().await?;
+    }
+    Ok(status)
+}
+
+/// Loads the tree from the server again and swaps it in, staying in the same directory if it is
+/// still there.
+pub async fn reload_tree(vdu: &RefCell<Vdu>) -> Result<(), loading::LoadError> {
+    let tree = loading::reload_path_tree().await?;
+    vdu.borrow_mut().set_tree(tree);
+    navigation::show_view_from_hash(vdu);
+    search::rerun();
+    Ok(())
+}
+
+/// Waits for the scan the server has started, then swaps in the tree it found.
+async fn swap_in_new_tree(vdu: &RefCell<Vdu>, started: Result<RescanStatus, loading::LoadError>) {
+    button().set_disabled(true);
+    let status = match started {
+        Ok(status) => wait_for_scan(status).await,
+        Err(error) => Err(error),
+    };
+    match status {
+        Ok(status) if status.error.is_some() => show_status(&status_text(&status)),
+        Ok(_) => match reload_tree(vdu).await {
+            Ok(()) => show_status(""),
+            Err(error) => show_status(&format!("couldn't load the new tree: {}", error)),
+        },
+        Err(error) => show_status(&format!("scan failed: {}", error)),
+    }
+    button().set_disabled(false);
+}
+
+async fn rescan(vdu: Rc<RefCell<Vdu>>) {
+    let path = vdu.borrow().view_path();
+    let started = loading::start_rescan(&path).await;
+    swap_in_new_tree(&vdu, started).await;
+}
+
+/// Shows the rescan button if the tree was scanned rather than imported. A scan that is already
+/// running, like one started from another tab, is waited for first.
+pub fn set_up_rescan(vdu: Rc<RefCell<Vdu>>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let status = match loading::rescan_status().await {
+            Ok(status) => status,
+            Err(error) => {
+                crate::log(&format!("couldn't get the scan status: {}", error));
+                return;
+            }
+        };
+        if !status.can_rescan {
+            return;
+        }
+
+        button().set_hidden(false);
+        if status.scanning.is_some() {
+            swap_in_new_tree(&vdu, Ok(status)).await;
+        }
+
+        let closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
+            wasm_bindgen_futures::spawn_local(rescan(vdu.clone()));
+        }) as Box<dyn FnMut(_)>);
+        button().set_onclick(Some(closure.as_ref().unchecked_ref()));
+        closure.forget();
+    });
+}
+
+#[test]
+fn status_text_shows_progress_or_error() {
+    let mut status = RescanStatus {
+        can_rescan: true,
+        scanning: Some("/home".into()),
+        entries: 12345,
+        error: None,
+    };
+    assert_eq!(status_text(&status), "scanning /home… 12345 entries");
+    status.scanning = None;
+    status.error = Some("/home: No such file".into());
+    assert_eq!(status_text(&status), "scan failed: /home: No such file");
+}
@@ -0,0 +1,137 @@
+// copyright 2021 Remi Bernotavicius
+
+//! The search box in the toolbar.
+
+use crate::vdu::Vdu;
+use crate::{loading, navigation, window};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use vdu_path_tree::{Query, SearchMode};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+fn input() -> web_sys::HtmlInputElement {
+    let document = window().document().unwrap();
+    document
+        .get_element_by_id("search")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlInputElement>()
+        .unwrap()
+}
+
+fn mode() -> String {
+    let document = window().document().unwrap();
+    let select = document
+        .get_element_by_id("search-mode")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlSelectElement>()
+        .unwrap();
+    select.value()
+}
+
+fn show_status(status: &str) {
+    let document = window().document().unwrap();
+    let element = document.get_element_by_id("search-status").unwrap();
+    element.set_text_content(Some(status));
+}
+
+/// Searches for what is in the search box. The search runs on the server, since most of the tree
+/// may not have been loaded; `latest` counts the searches so the results of one that was
+/// overtaken by more typing are thrown away.
+fn run(vdu: &Rc<RefCell<Vdu>>, latest: &Rc<Cell<u32>>) {
+    let search = latest.get().wrapping_add(1);
+    latest.set(search);
+
+    let pattern = input().value();
+    if pattern.is_empty() {
+        vdu.borrow_mut().set_search(None);
+        show_status("");
+        return;
+    }
+
+    // checking the pattern here says what is wrong with it without waiting for the server
+    let mode = mode();
+    let query = mode
+        .parse::<SearchMode>()
+        .and_then(|m| Query::new(m, &pattern).map_err(|e| e.to_string()));
+    if let Err(error) = query {
+        vdu.borrow_mut().set_search(None);
+        show_status(&format!("invalid pattern: {}", error));
+        return;
+    }
+
+    show_status("searching…");
+    let vdu = vdu.clone();
+    let latest = latest.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let response = loading::search(&mode, &pattern).await;
+        if latest.get() != search {
+            return;
+        }
+        match response {
+            Ok(response) => {
+                vdu.borrow_mut().set_search(Some(response));
+                show_status(&vdu.borrow().search_summary().unwrap_or_default());
+            }
+            Err(error) => {
+                vdu.borrow_mut().set_search(None);
+                show_status(&format!("search failed: {}", error));
+            }
+        }
+    });
+}
+
+/// Runs the search in the search box again, like after the tree changed.
+pub fn rerun() {
+    let event = web_sys::Event::new("input").unwrap();
+    input().dispatch_event(&event).unwrap();
+}
+
+/// Goes to the next or previous match.
+pub fn step(vdu: &RefCell<Vdu>, delta: isize) {
+    let view = vdu.borrow_mut().step_search(delta);
+    if let Some(view) = view {
+        navigation::go_to(&view);
+    }
+    show_status(&vdu.borrow().search_summary().unwrap_or_default());
+}
+
+fn add_listener(id: &str, event: &str, f: impl FnMut(web_sys::Event) + 'static) {
+    let document = window().document().unwrap();
+    let element = document.get_element_by_id(id).unwrap();
+    let closure = Closure::wrap(Box::new(f) as Box<dyn FnMut(_)>);
+    element
+        .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+}
+
+pub fn set_up_search(vdu: Rc<RefCell<Vdu>>) {
+    let latest = Rc::new(Cell::new(0));
+
+    let vdu_for_input = vdu.clone();
+    let latest_for_input = latest.clone();
+    add_listener("search", "input", move |_| {
+        run(&vdu_for_input, &latest_for_input)
+    });
+
+    let vdu_for_mode = vdu.clone();
+    add_listener("search-mode", "change", move |_| {
+        run(&vdu_for_mode, &latest)
+    });
+
+    // enter goes to the next match, and shift + enter to the previous one
+    let vdu_for_enter = vdu.clone();
+    add_listener("search", "keydown", move |event| {
+        let event = event.dyn_into::<web_sys::KeyboardEvent>().unwrap();
+        if event.key() == "Enter" {
+            step(&vdu_for_enter, if event.shift_key() { -1 } else { 1 });
+        }
+    });
+
+    let vdu_for_previous = vdu.clone();
+    add_listener("search-previous", "click", move |_| {
+        step(&vdu_for_previous, -1)
+    });
+    add_listener("search-next", "click", move |_| step(&vdu, 1));
+}
@@ -0,0 +1,196 @@
+// copyright 2021 Remi Bernotavicius
+
+//! A table of what is in the directory being viewed, for comparing sizes more precisely than the
+//! treemap allows. Rows and rectangles are highlighted together.
+
+use crate::vdu::Vdu;
+use crate::{fit_canvas_to_window, navigation, window};
+use bytesize::ByteSize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use vdu_path_tree::PathTreeNode;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Directories with more children than this only list the largest ones.
+const MAX_ROWS: usize = 500;
+
+#[derive(Debug, PartialEq)]
+struct Row {
+    name: String,
+    num_bytes: u64,
+    percent: f64,
+    /// How many entries are below it, for directories.
+    entries: Option<usize>,
+}
+
+/// The children of `node`, largest first.
+fn rows(node: &PathTreeNode) -> Vec<Row> {
+    let total = node.num_bytes();
+    let mut rows: Vec<_> = node
+        .children()
+        .map(|(name, child)| Row {
+            name: name.into(),
+            num_bytes: child.num_bytes(),
+            percent: if total == 0 {
+                0.0
+            } else {
+                child.num_bytes() as f64 / total as f64 * 100.0
+            },
+            entries: child.is_directory().then(|| child.size() - 1),
+        })
+        .collect();
+    rows.sort_by(|a, b| b.num_bytes.cmp(&a.num_bytes).then(a.name.cmp(&b.name)));
+    rows
+}
+
+fn tbody() -> web_sys::Element {
+    let document = window().document().unwrap();
+    document.get_element_by_id("sidebar-rows").unwrap()
+}
+
+fn add_cell(row: &web_sys::Element, text: &str, class: &str) {
+    let document = window().document().unwrap();
+    let cell = document.create_element("td").unwrap();
+    cell.set_class_name(class);
+    cell.set_text_content(Some(text));
+    row.append_child(&cell).unwrap();
+}
+
+/// Fills the table with the children of the directory being viewed.
+pub fn update(vdu: &Vdu) {
+    let document = window().document().unwrap();
+    let tbody = tbody();
+    tbody.set_text_content(None);
+
+    let rows = vdu.view_node().map(rows).unwrap_or_default();
+    for row in rows.iter().take(MAX_ROWS) {
+        let tr = document.create_element("tr").unwrap();
+        tr.set_attribute("data-name", &row.name).unwrap();
+        if row.entries.is_some() {
+            tr.set_attribute("data-directory", "").unwrap();
+        }
+        let name = match row.entries {
+            Some(_) => format!("{}/", row.name),
+            None => row.name.clone(),
+        };
+        add_cell(&tr, &name, "name");
+        add_cell(&tr, &ByteSize::b(row.num_bytes).to_string(), "number");
+        add_cell(&tr, &format!("{:.1}%", row.percent), "number");
+        let entries = row.entries.map(|e| e.to_string()).unwrap_or_default();
+        add_cell(&tr, &entries, "number");
+        tbody.append_child(&tr).unwrap();
+    }
+
+    if rows.len() > MAX_ROWS {
+        let tr = document.create_element("tr").unwrap();
+        add_cell(&tr, &format!("({} more)", rows.len() - MAX_ROWS), "name");
+        tbody.append_child(&tr).unwrap();
+    }
+}
+
+/// Marks the row for the child with the given name, unmarking the others.
+pub fn highlight_row(name: Option<&str>) {
+    let rows = tbody().children();
+    for i in 0..rows.length() {
+        let row = rows.item(i).unwrap();
+        let matches = name.is_some() && row.get_attribute("data-name").as_deref() == name;
+        row.set_class_name(if matches { "highlighted" } else { "" });
+    }
+}
+
+/// The row an event on the table happened in.
+fn event_row(event: &web_sys::Event) -> Option<web_sys::Element> {
+    let target = event.target()?.dyn_into::<web_sys::Element>().ok()?;
+    target.closest("tr[data-name]").ok()?
+}
+
+fn add_listener(
+    target: &web_sys::EventTarget,
+    event: &str,
+    f: impl FnMut(web_sys::Event) + 'static,
+) {
+    let closure = Closure::wrap(Box::new(f) as Box<dyn FnMut(_)>);
+    target
+        .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+}
+
+pub fn set_up_sidebar(vdu: Rc<RefCell<Vdu>>) {
+    let tbody = tbody();
+
+    // the listeners are on the whole table so they don't need adding again for every row
+    let vdu_for_hover = vdu.clone();
+    add_listener(&tbody, "mouseover", move |event| {
+        let name = event_row(&event).and_then(|r| r.get_attribute("data-name"));
+        highlight_row(name.as_deref());
+        vdu_for_hover.borrow_mut().set_highlighted(name);
+    });
+
+    let vdu_for_leave = vdu.clone();
+    add_listener(&tbody, "mouseleave", move |_| {
+        highlight_row(None);
+        vdu_for_leave.borrow_mut().set_highlighted(None);
+    });
+
+    let vdu_for_click = vdu.clone();
+    add_listener(&tbody, "click", move |event| {
+        let row = match event_row(&event) {
+            Some(row) if row.has_attribute("data-directory") => row,
+            _ => return,
+        };
+        let mut view = vdu_for_click.borrow().view().to_vec();
+        view.extend(row.get_attribute("data-name"));
+        navigation::go_to(&view);
+    });
+
+    let document = window().document().unwrap();
+    let toggle = document.get_element_by_id("sidebar-toggle").unwrap();
+    add_listener(&toggle, "click", move |_| {
+        let sidebar = document.get_element_by_id("sidebar").unwrap();
+        let collapsed = sidebar.class_name() == "collapsed";
+        sidebar.set_class_name(if collapsed { "" } else { "collapsed" });
+        fit_canvas_to_window(&vdu);
+    });
+}
+
+#[test]
+fn rows_are_largest_first_with_shares() {
+    use std::path::Path;
+    use vdu_path_tree::{FileType, NodeMetadata, PathTree};
+
+    let mut tree = PathTree::empty();
+    for &(path, num_bytes) in &[("/r", 0), ("/r/a", 1), ("/r/b", 0), ("/r/b/c", 3)] {
+        tree.add_path(Path::new(path), num_bytes, NodeMetadata::unknown());
+    }
+    let empty_dir = NodeMetadata {
+        file_type: FileType::Directory,
+        modified: None,
+    };
+    tree.add_path(Path::new("/r/empty"), 0, empty_dir);
+
+    assert_eq!(
+        rows(tree.root().unwrap()),
+        [
+            Row {
+                name: "b".into(),
+                num_bytes: 3,
+                percent: 75.0,
+                entries: Some(1),
+            },
+            Row {
+                name: "a".into(),
+                num_bytes: 1,
+                percent: 25.0,
+                entries: None,
+            },
+            Row {
+                name: "empty".into(),
+                num_bytes: 0,
+                percent: 0.0,
+                entries: Some(0),
+            },
+        ]
+    );
+}
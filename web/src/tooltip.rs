@@ -0,0 +1,134 @@
+// copyright 2021 Remi Bernotavicius
+
+//! The box following the mouse with details about the entry under it.
+
+use crate::window;
+use bytesize::ByteSize;
+use vdu_path_tree::{format_timestamp, FileType, PathTreeNode};
+use wasm_bindgen::JsCast;
+
+/// Distance between the mouse and the tooltip.
+const OFFSET: i32 = 14;
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64 * 100.0
+    }
+}
+
+/// The lines of the tooltip for `node`, given the sizes of its parent (if it is shown) and of
+/// the whole tree.
+pub fn details(node: &PathTreeNode, parent_bytes: Option<u64>, total_bytes: u64) -> Vec<String> {
+    let num_bytes = node.num_bytes();
+    let mut lines = vec![
+        node.path().display().to_string(),
+        format!("{} ({} bytes)", ByteSize::b(num_bytes), num_bytes),
+    ];
+
+    // directories are counted along with files, since a directory that hasn't been loaded yet
+    // only knows how many entries it holds in total
+    match node.size() - 1 {
+        0 => {}
+        1 => lines.push("1 entry inside".into()),
+        n => lines.push(format!("{} entries inside (files and directories)", n)),
+    }
+
+    let of_total = format!("{:.1}% of total", percent(num_bytes, total_bytes));
+    lines.push(match parent_bytes {
+        Some(parent_bytes) => format!(
+            "{:.1}% of parent, {}",
+            percent(num_bytes, parent_bytes),
+            of_total
+        ),
+        None => of_total,
+    });
+
+    let metadata = node.metadata();
+    if metadata.file_type != FileType::Unknown {
+        lines.push(format!("type: {}", metadata.file_type.as_str()));
+    }
+    if let Some(modified) = metadata.modified {
+        lines.push(format!("modified: {}", format_timestamp(modified)));
+    }
+    lines
+}
+
+fn element() -> web_sys::HtmlElement {
+    let document = window().document().unwrap();
+    document
+        .get_element_by_id("tooltip")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlElement>()
+        .unwrap()
+}
+
+/// Shows `lines` next to the mouse at (x, y) in the window, keeping the tooltip on screen.
+pub fn show(lines: &[String], x: i32, y: i32) {
+    let tooltip = element();
+    tooltip.set_text_content(Some(&lines.join("\n")));
+    tooltip.set_hidden(false);
+
+    let document = window().document().unwrap();
+    let page = document.document_element().unwrap();
+    let mut left = x + OFFSET;
+    if left + tooltip.offset_width() > page.client_width() {
+        left = (x - OFFSET - tooltip.offset_width()).max(0);
+    }
+    let mut top = y + OFFSET;
+    if top + tooltip.offset_height() > page.client_height() {
+        top = (y - OFFSET - tooltip.offset_height()).max(0);
+    }
+
+    let style = tooltip.style();
+    style.set_property("left", &format!("{}px", left)).unwrap();
+    style.set_property("top", &format!("{}px", top)).unwrap();
+}
+
+pub fn hide() {
+    element().set_hidden(true);
+}
+
+#[test]
+fn details_include_shares_and_metadata() {
+    use std::path::Path;
+    use vdu_path_tree::{NodeMetadata, PathTree};
+
+    let file = NodeMetadata {
+        file_type: FileType::File,
+        modified: Some(0),
+    };
+    let mut tree = PathTree::empty();
+    tree.add_path(Path::new("/r"), 0, NodeMetadata::unknown());
+    tree.add_path(Path::new("/r/a"), 1500, file);
+    tree.add_path(Path::new("/r/b"), 500, file);
+
+    let node = tree.get(Path::new("/r/a")).unwrap();
+    assert_eq!(
+        details(node, Some(2000), 4000),
+        [
+            "/r/a",
+            "1.5 KiB (1500 bytes)",
+            "75.0% of parent, 37.5% of total",
+            "type: file",
+            "modified: 1970-01-01 00:00 UTC",
+        ]
+    );
+
+    let root = tree.root().unwrap();
+    assert_eq!(
+        details(root, None, 2000),
+        [
+            "/r",
+            "2.0 KiB (2000 bytes)",
+            "2 entries inside (files and directories)",
+            "100.0% of total"
+        ]
+    );
+    tree.remove_path(Path::new("/r/b"));
+    assert_eq!(
+        details(tree.root().unwrap(), None, 2000)[2],
+        "1 entry inside"
+    );
+}
@@ -0,0 +1,215 @@
+// copyright 2021 Remi Bernotavicius
+
+//! Touch gestures and trackpad scrolling over the treemap. A tap selects like the arrow keys do, a
+//! double tap goes into a directory like a click, and pinching or dragging with two fingers
+//! magnifies and moves the treemap.
+
+use crate::vdu::Vdu;
+use crate::{canvas, navigation, sidebar, tooltip};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// How far a finger can move, in CSS pixels, and still count as tapping.
+const TAP_SLOP: f64 = 10.0;
+
+/// The longest time between the taps of a double tap, in milliseconds.
+const DOUBLE_TAP_TIME: f64 = 300.0;
+
+/// How much scrolling with ctrl held (which is what pinching on a trackpad sends) magnifies.
+const WHEEL_ZOOM_SPEED: f64 = 0.01;
+
+type Point = (f64, f64);
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+fn center(a: Point, b: Point) -> Point {
+    ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+}
+
+#[derive(Clone, Copy)]
+struct Tap {
+    position: Point,
+    time: f64,
+}
+
+impl Tap {
+    fn is_double(&self, first: &Tap) -> bool {
+        self.time - first.time < DOUBLE_TAP_TIME
+            && distance(self.position, first.position) < TAP_SLOP * 2.0
+    }
+}
+
+#[derive(Default)]
+struct Gesture {
+    /// Where the fingers were on the last event.
+    previous: Vec<Point>,
+    /// Where a single finger went down, as long as it might still be a tap.
+    tap: Option<Tap>,
+    last_tap: Option<Tap>,
+}
+
+/// The points on the canvas being touched.
+fn points(touches: &web_sys::TouchList) -> Vec<Point> {
+    let rect = canvas().get_bounding_client_rect();
+    (0..touches.length())
+        .filter_map(|i| touches.item(i))
+        .map(|t| {
+            (
+                t.client_x() as f64 - rect.left(),
+                t.client_y() as f64 - rect.top(),
+            )
+        })
+        .collect()
+}
+
+fn on_touch_start(gesture: &mut Gesture, event: &web_sys::TouchEvent) {
+    let points = points(&event.touches());
+    gesture.tap = match &points[..] {
+        &[position] => Some(Tap {
+            position,
+            time: event.time_stamp(),
+        }),
+        _ => None,
+    };
+    gesture.previous = points;
+}
+
+fn on_touch_move(gesture: &mut Gesture, vdu: &RefCell<Vdu>, event: &web_sys::TouchEvent) {
+    let points = points(&event.touches());
+    let mut vdu = vdu.borrow_mut();
+    match (&gesture.previous[..], &points[..]) {
+        (&[a, b], &[c, d]) => {
+            let (old_center, new_center) = (center(a, b), center(c, d));
+            let old_distance = distance(a, b);
+            if old_distance > 0.0 {
+                vdu.zoom_at(distance(c, d) / old_distance, old_center.0, old_center.1);
+            }
+            vdu.pan(new_center.0 - old_center.0, new_center.1 - old_center.1);
+        }
+        (&[a], &[b]) => {
+            if let Some(tap) = gesture.tap {
+                if distance(tap.position, b) > TAP_SLOP {
+                    gesture.tap = None;
+                }
+            }
+            // one finger only moves the treemap once it has been magnified, otherwise
+            // a sloppy tap would do nothing
+            if gesture.tap.is_none() && vdu.is_magnified() {
+                vdu.pan(b.0 - a.0, b.1 - a.1);
+            }
+        }
+        _ => {}
+    }
+    gesture.previous = points;
+}
+
+fn on_touch_end(gesture: &mut Gesture, vdu: &RefCell<Vdu>, event: &web_sys::TouchEvent) {
+    gesture.previous = points(&event.touches());
+    if !gesture.previous.is_empty() {
+        return;
+    }
+    let tap = match gesture.tap.take() {
+        Some(tap) => tap,
+        None => return,
+    };
+    let (x, y) = tap.position;
+
+    if gesture
+        .last_tap
+        .take()
+        .is_some_and(|first| tap.is_double(&first))
+    {
+        let new_view = vdu.borrow().on_click(x, y);
+        if let Some(new_view) = new_view {
+            tooltip::hide();
+            navigation::go_to(&new_view);
+        }
+        return;
+    }
+    gesture.last_tap = Some(tap);
+
+    let mut vdu = vdu.borrow_mut();
+    vdu.on_mouse_move(x, y);
+    vdu.select_at(x, y);
+    sidebar::highlight_row(vdu.hovered_child().as_deref());
+    let rect = canvas().get_bounding_client_rect();
+    match vdu.hover_details() {
+        Some(lines) => tooltip::show(&lines, (x + rect.left()) as i32, (y + rect.top()) as i32),
+        None => tooltip::hide(),
+    }
+}
+
+fn on_wheel(vdu: &RefCell<Vdu>, event: &web_sys::WheelEvent) {
+    let (x, y) = (event.offset_x() as f64, event.offset_y() as f64);
+    let mut vdu = vdu.borrow_mut();
+    if event.ctrl_key() {
+        vdu.zoom_at((-event.delta_y() * WHEEL_ZOOM_SPEED).exp(), x, y);
+    } else if vdu.is_magnified() {
+        vdu.pan(-event.delta_x(), -event.delta_y());
+    } else {
+        return;
+    }
+    event.prevent_default();
+}
+
+fn add_listener<E: JsCast + 'static>(event: &str, mut f: impl FnMut(&E) + 'static) {
+    let closure = Closure::wrap(
+        Box::new(move |event: web_sys::Event| f(event.unchecked_ref())) as Box<dyn FnMut(_)>,
+    );
+    canvas()
+        .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+        .unwrap();
+    closure.forget();
+}
+
+pub fn set_up_touch(vdu: Rc<RefCell<Vdu>>) {
+    let gesture = Rc::new(RefCell::new(Gesture::default()));
+
+    let gesture_for_start = gesture.clone();
+    add_listener("touchstart", move |event: &web_sys::TouchEvent| {
+        on_touch_start(&mut gesture_for_start.borrow_mut(), event);
+    });
+
+    // stopping the default keeps the page still, and stops the browser making up mouse events
+    // which would click on whatever was tapped
+    let gesture_for_move = gesture.clone();
+    let vdu_for_move = vdu.clone();
+    add_listener("touchmove", move |event: &web_sys::TouchEvent| {
+        event.prevent_default();
+        on_touch_move(&mut gesture_for_move.borrow_mut(), &vdu_for_move, event);
+    });
+
+    let gesture_for_end = gesture.clone();
+    let vdu_for_end = vdu.clone();
+    add_listener("touchend", move |event: &web_sys::TouchEvent| {
+        event.prevent_default();
+        on_touch_end(&mut gesture_for_end.borrow_mut(), &vdu_for_end, event);
+    });
+
+    add_listener("touchcancel", move |_: &web_sys::TouchEvent| {
+        *gesture.borrow_mut() = Gesture::default();
+    });
+
+    add_listener("wheel", move |event: &web_sys::WheelEvent| {
+        on_wheel(&vdu, event)
+    });
+}
+
+#[test]
+fn double_tap_needs_taps_close_together() {
+    let first = Tap {
+        position: (100.0, 100.0),
+        time: 1000.0,
+    };
+    let tap = |x, time| Tap {
+        position: (x, 100.0),
+        time,
+    };
+    assert!(tap(105.0, 1200.0).is_double(&first));
+    assert!(!tap(105.0, 1400.0).is_double(&first));
+    assert!(!tap(150.0, 1200.0).is_double(&first));
+}
@@ -1,12 +1,14 @@
-pub const COLOR_NAMES: &'static [&'static str] = &[
-    "AliceBlue",
-    "AntiqueWhite",
+// copyright 2021 Remi Bernotavicius
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// CSS color names, leaving out ones too close to the white background to be seen.
+pub const COLOR_NAMES: &[&str] = &[
     "Aqua",
     "Aquamarine",
-    "Azure",
-    "Beige",
     "Bisque",
-    "BlanchedAlmond",
     "Blue",
     "BlueViolet",
     "Brown",
@@ -16,7 +18,6 @@ pub const COLOR_NAMES: &'static [&'static str] = &[
     "Chocolate",
     "Coral",
     "CornflowerBlue",
-    "Cornsilk",
     "Crimson",
     "Cyan",
     "DarkBlue",
@@ -44,31 +45,21 @@ pub const COLOR_NAMES: &'static [&'static str] = &[
     "DimGrey",
     "DodgerBlue",
     "FireBrick",
-    "FloralWhite",
     "ForestGreen",
     "Fuchsia",
-    "Gainsboro",
-    "GhostWhite",
     "Gold",
     "GoldenRod",
     "Gray",
     "Green",
     "GreenYellow",
     "Grey",
-    "HoneyDew",
     "HotPink",
     "IndianRed",
     "Indigo",
-    "Ivory",
     "Khaki",
-    "Lavender",
-    "LavenderBlush",
     "LawnGreen",
-    "LemonChiffon",
     "LightBlue",
     "LightCoral",
-    "LightCyan",
-    "LightGoldenRodYellow",
     "LightGray",
     "LightGreen",
     "LightGrey",
@@ -79,10 +70,8 @@ pub const COLOR_NAMES: &'static [&'static str] = &[
     "LightSlateGray",
     "LightSlateGrey",
     "LightSteelBlue",
-    "LightYellow",
     "Lime",
     "LimeGreen",
-    "Linen",
     "Magenta",
     "Maroon",
     "MediumAquaMarine",
@@ -95,12 +84,9 @@ pub const COLOR_NAMES: &'static [&'static str] = &[
     "MediumTurquoise",
     "MediumVioletRed",
     "MidnightBlue",
-    "MintCream",
-    "MistyRose",
     "Moccasin",
     "NavajoWhite",
     "Navy",
-    "OldLace",
     "Olive",
     "OliveDrab",
     "Orange",
@@ -110,7 +96,6 @@ pub const COLOR_NAMES: &'static [&'static str] = &[
     "PaleGreen",
     "PaleTurquoise",
     "PaleVioletRed",
-    "PapayaWhip",
     "PeachPuff",
     "Peru",
     "Pink",
@@ -125,14 +110,12 @@ pub const COLOR_NAMES: &'static [&'static str] = &[
     "Salmon",
     "SandyBrown",
     "SeaGreen",
-    "SeaShell",
     "Sienna",
     "Silver",
     "SkyBlue",
     "SlateBlue",
     "SlateGray",
     "SlateGrey",
-    "Snow",
     "SpringGreen",
     "SteelBlue",
     "Tan",
@@ -142,7 +125,306 @@ pub const COLOR_NAMES: &'static [&'static str] = &[
     "Turquoise",
     "Violet",
     "Wheat",
-    "WhiteSmoke",
     "Yellow",
     "YellowGreen",
 ];
+
+/// Colors that are easy to tell apart, for schemes with a handful of groups.
+const PALETTE: &[&str] = &[
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+    "#9c755f",
+];
+
+/// For entries that don't fall into any group.
+const OTHER_COLOR: &str = "#bab0ac";
+
+/// Chooses a color based on the hash of the input.
+pub fn hash_color<T: Hash>(t: T) -> &'static str {
+    let mut s = DefaultHasher::new();
+    t.hash(&mut s);
+    COLOR_NAMES[(s.finish() as usize) % COLOR_NAMES.len()]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Category {
+    Video,
+    Audio,
+    Image,
+    Code,
+    Archive,
+    Document,
+    Binary,
+    Directory,
+}
+
+const CATEGORIES: &[Category] = &[
+    Category::Video,
+    Category::Audio,
+    Category::Image,
+    Category::Code,
+    Category::Archive,
+    Category::Document,
+    Category::Binary,
+    Category::Directory,
+];
+
+impl Category {
+    fn of(name: &str, is_directory: bool) -> Option<Self> {
+        if is_directory {
+            return Some(Self::Directory);
+        }
+        let (_, extension) = name.rsplit_once('.')?;
+        let category = match &extension.to_ascii_lowercase()[..] {
+            "mp4" | "mkv" | "avi" | "mov" | "wmv" | "webm" | "flv" | "m4v" | "mpg" | "mpeg" => {
+                Self::Video
+            }
+            "mp3" | "flac" | "wav" | "ogg" | "m4a" | "aac" | "opus" | "wma" => Self::Audio,
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "svg" | "webp" | "tif" | "tiff" | "ico"
+            | "heic" | "psd" => Self::Image,
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "py" | "js" | "ts" | "go" | "java" | "rb"
+            | "sh" | "html" | "css" | "json" | "toml" | "yaml" | "yml" | "xml" | "kt" | "swift"
+            | "php" | "pl" | "lua" | "sql" => Self::Code,
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "7z" | "rar" | "zst" | "iso" | "deb"
+            | "rpm" | "dmg" | "jar" => Self::Archive,
+            "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "ods" | "txt"
+            | "md" | "rtf" | "epub" | "csv" => Self::Document,
+            "exe" | "dll" | "so" | "dylib" | "o" | "a" | "bin" | "class" | "wasm" | "rlib"
+            | "pyc" | "lib" | "obj" => Self::Binary,
+            _ => return None,
+        };
+        Some(category)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Video => "video",
+            Self::Audio => "audio",
+            Self::Image => "images",
+            Self::Code => "code",
+            Self::Archive => "archives",
+            Self::Document => "documents",
+            Self::Binary => "binaries",
+            Self::Directory => "directories",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Self::Directory => "#8c8c8c",
+            _ => PALETTE[*self as usize],
+        }
+    }
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// The oldest an entry can be to fall in each age group.
+const AGES: &[(&str, u64, &str)] = &[
+    ("last day", DAY, "#d73027"),
+    ("last week", 7 * DAY, "#fc8d59"),
+    ("last month", 30 * DAY, "#fee090"),
+    ("last year", 365 * DAY, "#91bfdb"),
+    ("older", u64::MAX, "#4575b4"),
+];
+
+const DEPTHS: &[&str] = &["#08519c", "#3182bd", "#6baed6", "#9ecae1", "#c6dbef"];
+
+/// How the rectangles in the treemap are colored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    /// By what kind of file it is, going by the extension.
+    #[default]
+    Category,
+    /// By which directory at the top of the tree it is in.
+    TopLevel,
+    /// By how long ago it was modified.
+    Age,
+    /// By how deep in the tree it is.
+    Depth,
+    /// By a hash of the path, so every entry looks different.
+    Hash,
+}
+
+impl FromStr for ColorScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "category" => Ok(Self::Category),
+            "top-level" => Ok(Self::TopLevel),
+            "age" => Ok(Self::Age),
+            "depth" => Ok(Self::Depth),
+            "hash" => Ok(Self::Hash),
+            _ => Err(format!("unknown color scheme '{}'", s)),
+        }
+    }
+}
+
+/// What is known about an entry when choosing its color.
+pub struct Entry<'a> {
+    /// The path from the root of the tree, like `/usr/share`.
+    pub path: &'a str,
+    pub is_directory: bool,
+    pub modified: Option<u64>,
+}
+
+impl Entry<'_> {
+    fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
+    }
+
+    fn top_level(&self) -> &str {
+        self.path.split('/').nth(1).unwrap_or_default()
+    }
+
+    fn depth(&self) -> usize {
+        self.path.matches('/').count()
+    }
+}
+
+/// What the color schemes need to know about the whole tree.
+pub struct Coloring {
+    pub scheme: ColorScheme,
+    /// Seconds since the unix epoch, for aging entries.
+    pub now: u64,
+    /// The largest directories at the top of the tree, largest first, which get their own colors.
+    pub top_level: Vec<String>,
+}
+
+impl Coloring {
+    pub fn color(&self, entry: &Entry<'_>) -> &'static str {
+        match self.scheme {
+            ColorScheme::Category => {
+                Category::of(entry.name(), entry.is_directory).map_or(OTHER_COLOR, |c| c.color())
+            }
+            ColorScheme::TopLevel => self
+                .top_level
+                .iter()
+                .position(|n| n == entry.top_level())
+                .map_or(OTHER_COLOR, |i| PALETTE[i]),
+            ColorScheme::Age => entry.modified.map_or(OTHER_COLOR, |modified| {
+                let age = self.now.saturating_sub(modified);
+                AGES.iter().find(|&&(_, max, _)| age < max).unwrap().2
+            }),
+            ColorScheme::Depth => DEPTHS[entry.depth().saturating_sub(1).min(DEPTHS.len() - 1)],
+            ColorScheme::Hash => hash_color(entry.path),
+        }
+    }
+
+    /// What each color means, for showing next to the treemap.
+    pub fn legend(&self) -> Vec<(String, &'static str)> {
+        let other = ("other".to_owned(), OTHER_COLOR);
+        match self.scheme {
+            ColorScheme::Category => CATEGORIES
+                .iter()
+                .map(|c| (c.name().to_owned(), c.color()))
+                .chain(Some(other))
+                .collect(),
+            ColorScheme::TopLevel => self
+                .top_level
+                .iter()
+                .cloned()
+                .zip(PALETTE.iter().cloned())
+                .chain(Some(other))
+                .collect(),
+            ColorScheme::Age => AGES
+                .iter()
+                .map(|&(name, _, color)| (name.to_owned(), color))
+                .chain(Some(("unknown".to_owned(), OTHER_COLOR)))
+                .collect(),
+            ColorScheme::Depth => DEPTHS
+                .iter()
+                .enumerate()
+                .map(|(i, &color)| {
+                    let plus = if i == DEPTHS.len() - 1 { "+" } else { "" };
+                    (format!("depth {}{}", i + 1, plus), color)
+                })
+                .collect(),
+            ColorScheme::Hash => vec![],
+        }
+    }
+
+    /// How many top-level directories get their own color.
+    pub fn max_top_level() -> usize {
+        PALETTE.len()
+    }
+}
+
+#[test]
+fn hash_color_same_for_same_input() {
+    let color1 = hash_color(1);
+    let color2 = hash_color(1);
+    assert_eq!(color1, color2);
+}
+
+#[test]
+fn hash_color_different_for_different_input() {
+    let color1 = hash_color(1);
+    let color2 = hash_color(2);
+    assert_ne!(color1, color2);
+}
+
+#[cfg(test)]
+fn test_coloring(scheme: ColorScheme) -> Coloring {
+    Coloring {
+        scheme,
+        now: 1000 * DAY,
+        top_level: vec!["home".into(), "usr".into()],
+    }
+}
+
+#[cfg(test)]
+fn file(path: &str) -> Entry<'_> {
+    Entry {
+        path,
+        is_directory: false,
+        modified: None,
+    }
+}
+
+#[test]
+fn category_goes_by_extension() {
+    let coloring = test_coloring(ColorScheme::Category);
+    assert_eq!(
+        coloring.color(&file("/a/movie.MKV")),
+        Category::Video.color()
+    );
+    assert_eq!(coloring.color(&file("/a/lib.rs")), Category::Code.color());
+    assert_eq!(
+        coloring.color(&file("/a/b.tar.gz")),
+        Category::Archive.color()
+    );
+    assert_eq!(coloring.color(&file("/a/Makefile")), OTHER_COLOR);
+
+    let dir = Entry {
+        is_directory: true,
+        ..file("/a/src.d")
+    };
+    assert_eq!(coloring.color(&dir), Category::Directory.color());
+}
+
+#[test]
+fn top_level_colors_the_largest_directories() {
+    let coloring = test_coloring(ColorScheme::TopLevel);
+    assert_eq!(coloring.color(&file("/home/a/b")), PALETTE[0]);
+    assert_eq!(coloring.color(&file("/usr")), PALETTE[1]);
+    assert_eq!(coloring.color(&file("/var/log")), OTHER_COLOR);
+    assert_eq!(coloring.legend().len(), 3);
+}
+
+#[test]
+fn age_and_depth_use_buckets() {
+    let coloring = test_coloring(ColorScheme::Age);
+    let modified = |days_ago: u64| Entry {
+        modified: Some(1000 * DAY - days_ago * DAY),
+        ..file("/a")
+    };
+    assert_eq!(coloring.color(&modified(0)), AGES[0].2);
+    assert_eq!(coloring.color(&modified(10)), AGES[2].2);
+    assert_eq!(coloring.color(&modified(900)), AGES[4].2);
+    assert_eq!(coloring.color(&file("/a")), OTHER_COLOR);
+
+    let coloring = test_coloring(ColorScheme::Depth);
+    assert_eq!(coloring.color(&file("/a")), DEPTHS[0]);
+    assert_eq!(coloring.color(&file("/a/b/c/d/e/f/g")), DEPTHS[4]);
+}
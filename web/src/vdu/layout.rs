@@ -0,0 +1,439 @@
+// copyright 2021 Remi Bernotavicius
+
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rectangle {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rectangle {
+    fn divide(&self, direction: Direction, left_percent: f64) -> (Self, Self) {
+        let right_percent = 1.0 - left_percent;
+        match direction {
+            Direction::Vertical => (
+                Self {
+                    x: self.x,
+                    y: self.y,
+                    width: self.width * left_percent,
+                    height: self.height,
+                },
+                Self {
+                    x: self.x + self.width * left_percent,
+                    y: self.y,
+                    width: self.width * right_percent,
+                    height: self.height,
+                },
+            ),
+            Direction::Horizontal => (
+                Self {
+                    x: self.x,
+                    y: self.y,
+                    width: self.width,
+                    height: self.height * left_percent,
+                },
+                Self {
+                    x: self.x,
+                    y: self.y + self.height * left_percent,
+                    width: self.width,
+                    height: self.height * right_percent,
+                },
+            ),
+        }
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    pub fn area(&self) -> f64 {
+        self.width * self.height
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// Shrinks the rectangle by `amount` on every side.
+    pub fn inset(&self, amount: f64) -> Self {
+        Self {
+            x: self.x + amount,
+            y: self.y + amount,
+            width: (self.width - 2.0 * amount).max(0.0),
+            height: (self.height - 2.0 * amount).max(0.0),
+        }
+    }
+}
+
+/// The index of the rectangle in `candidates` closest to `from` in the direction (dx, dy), which
+/// should be one of the four unit directions. Rectangles off to the side count as further away
+/// than ones straight ahead.
+pub fn nearest_in_direction(
+    from: Rectangle,
+    candidates: &[Rectangle],
+    (dx, dy): (f64, f64),
+) -> Option<usize> {
+    let (from_x, from_y) = from.center();
+    let distance = |rect: &Rectangle| {
+        let (x, y) = rect.center();
+        let ahead = (x - from_x) * dx + (y - from_y) * dy;
+        let aside = ((x - from_x) * dy - (y - from_y) * dx).abs();
+        (ahead > 0.0).then_some(ahead + 2.0 * aside)
+    };
+    candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| distance(r).map(|d| (i, d)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+/// Height of the strip along the top of a directory showing its name and size.
+const HEADER_HEIGHT: f64 = 16.0;
+
+/// Width of the border drawn around a directory.
+const FRAME_BORDER: f64 = 2.0;
+
+/// Narrower directories don't get a header.
+const MIN_HEADER_WIDTH: f64 = 40.0;
+
+/// The parts of a rectangle a directory is drawn in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub header: Option<Rectangle>,
+    /// Where the directory's children are laid out.
+    pub content: Rectangle,
+}
+
+impl Frame {
+    /// Frames `rect` with a border, and a header strip if there is room for one along with the
+    /// content below it.
+    pub fn new(rect: Rectangle) -> Self {
+        let inner = rect.inset(FRAME_BORDER);
+        if inner.width < MIN_HEADER_WIDTH || inner.height < HEADER_HEIGHT * 3.0 {
+            return Self {
+                header: None,
+                content: inner,
+            };
+        }
+
+        Self {
+            header: Some(Rectangle {
+                height: HEADER_HEIGHT,
+                ..inner
+            }),
+            content: Rectangle {
+                y: inner.y + HEADER_HEIGHT,
+                height: inner.height - HEADER_HEIGHT,
+                ..inner
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+impl Direction {
+    fn next(&self) -> Self {
+        match self {
+            Self::Horizontal => Self::Vertical,
+            Self::Vertical => Self::Horizontal,
+        }
+    }
+}
+
+/// How a rectangle is divided up between the children of a directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// Rows of rectangles kept as close to square as possible.
+    #[default]
+    Squarified,
+    /// Halves the children by count, alternating between splitting vertically and horizontally.
+    BinarySplit,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "squarified" => Ok(Self::Squarified),
+            "binary" => Ok(Self::BinarySplit),
+            _ => Err(format!("unknown layout '{}'", s)),
+        }
+    }
+}
+
+impl Layout {
+    /// Divides `rect` between items with the given sizes, returning a rectangle for each item in
+    /// the same order.
+    pub fn divide(&self, rect: Rectangle, sizes: &[u64]) -> Vec<Rectangle> {
+        match self {
+            Self::Squarified => squarify(rect, sizes),
+            Self::BinarySplit => binary_split(rect, sizes),
+        }
+    }
+}
+
+fn binary_split(rect: Rectangle, sizes: &[u64]) -> Vec<Rectangle> {
+    fn divide(rect: Rectangle, sizes: &[u64], direction: Direction, out: &mut Vec<Rectangle>) {
+        if sizes.len() == 1 {
+            out.push(rect);
+            return;
+        }
+
+        let (left_sizes, right_sizes) = sizes.split_at(sizes.len() / 2);
+        let left_sum: u64 = left_sizes.iter().sum();
+        let right_sum: u64 = right_sizes.iter().sum();
+        let total = left_sum + right_sum;
+        let left_percent = if total == 0 {
+            0.5
+        } else {
+            left_sum as f64 / total as f64
+        };
+
+        let (left_rect, right_rect) = rect.divide(direction, left_percent);
+        divide(left_rect, left_sizes, direction.next(), out);
+        divide(right_rect, right_sizes, direction.next(), out);
+    }
+
+    let mut out = Vec::with_capacity(sizes.len());
+    if !sizes.is_empty() {
+        divide(rect, sizes, Direction::Vertical, &mut out);
+    }
+    out
+}
+
+/// The worst aspect ratio of a row of `areas` laid along a side of length `side`.
+fn worst_aspect_ratio(areas: &[f64], side: f64) -> f64 {
+    let sum: f64 = areas.iter().sum();
+    let max = areas.iter().cloned().fold(f64::MIN, f64::max);
+    let min = areas.iter().cloned().fold(f64::MAX, f64::min);
+    let side_squared = side * side;
+    let sum_squared = sum * sum;
+    f64::max(
+        side_squared * max / sum_squared,
+        sum_squared / (side_squared * min),
+    )
+}
+
+/// The squarified treemap algorithm from Bruls, Huizing and van Wijk: the largest items are placed
+/// first, and items are added to the current row along the short side of the remaining space for
+/// as long as that doesn't make the row's worst aspect ratio worse.
+fn squarify(rect: Rectangle, sizes: &[u64]) -> Vec<Rectangle> {
+    let empty = Rectangle {
+        x: rect.x,
+        y: rect.y,
+        width: 0.0,
+        height: 0.0,
+    };
+    let mut out = vec![empty; sizes.len()];
+
+    let total: u64 = sizes.iter().sum();
+    if total == 0 || rect.area() <= 0.0 {
+        return out;
+    }
+
+    let mut order: Vec<usize> = (0..sizes.len()).filter(|&i| sizes[i] > 0).collect();
+    order.sort_by(|&a, &b| sizes[b].cmp(&sizes[a]));
+    let scale = rect.area() / total as f64;
+    let areas: Vec<f64> = order.iter().map(|&i| sizes[i] as f64 * scale).collect();
+
+    let mut remaining = rect;
+    let mut start = 0;
+    while start < order.len() {
+        let side = remaining.width.min(remaining.height);
+        let mut end = start + 1;
+        while end < order.len()
+            && worst_aspect_ratio(&areas[start..=end], side)
+                <= worst_aspect_ratio(&areas[start..end], side)
+        {
+            end += 1;
+        }
+
+        // The last row takes all the remaining space, so rounding errors don't leave gaps.
+        let row_area: f64 = areas[start..end].iter().sum();
+        let is_last = end == order.len();
+        let mut offset = 0.0;
+        if remaining.width >= remaining.height {
+            // a column on the left
+            let width = if is_last {
+                remaining.width
+            } else {
+                row_area / remaining.height
+            };
+            for (&i, &area) in order[start..end].iter().zip(&areas[start..end]) {
+                let height = area / row_area * remaining.height;
+                out[i] = Rectangle {
+                    x: remaining.x,
+                    y: remaining.y + offset,
+                    width,
+                    height,
+                };
+                offset += height;
+            }
+            remaining.x += width;
+            remaining.width -= width;
+        } else {
+            // a row along the top
+            let height = if is_last {
+                remaining.height
+            } else {
+                row_area / remaining.width
+            };
+            for (&i, &area) in order[start..end].iter().zip(&areas[start..end]) {
+                let width = area / row_area * remaining.width;
+                out[i] = Rectangle {
+                    x: remaining.x + offset,
+                    y: remaining.y,
+                    width,
+                    height,
+                };
+                offset += width;
+            }
+            remaining.y += height;
+            remaining.height -= height;
+        }
+        start = end;
+    }
+    out
+}
+
+#[cfg(test)]
+fn rect(x: f64, y: f64, width: f64, height: f64) -> Rectangle {
+    Rectangle {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+fn assert_close(actual: &[Rectangle], expected: &[Rectangle]) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, e) in actual.iter().zip(expected) {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(
+            close(a.x, e.x)
+                && close(a.y, e.y)
+                && close(a.width, e.width)
+                && close(a.height, e.height),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn squarify_matches_paper_example() {
+    // the example from the paper: a 6x4 rectangle divided between areas 6, 6, 4, 3, 2, 2, 1
+    let rects = squarify(rect(0.0, 0.0, 6.0, 4.0), &[6, 6, 4, 3, 2, 2, 1]);
+    assert_close(
+        &rects,
+        &[
+            rect(0.0, 0.0, 3.0, 2.0),
+            rect(0.0, 2.0, 3.0, 2.0),
+            rect(3.0, 0.0, 12.0 / 7.0, 7.0 / 3.0),
+            rect(3.0 + 12.0 / 7.0, 0.0, 9.0 / 7.0, 7.0 / 3.0),
+            rect(3.0, 7.0 / 3.0, 1.2, 5.0 / 3.0),
+            rect(4.2, 7.0 / 3.0, 1.2, 5.0 / 3.0),
+            rect(5.4, 7.0 / 3.0, 0.6, 5.0 / 3.0),
+        ],
+    );
+}
+
+#[test]
+fn squarify_fills_rectangle() {
+    let sizes = [6, 6, 4, 3, 2, 2, 1];
+    let rects = squarify(rect(10.0, 20.0, 6.0, 4.0), &sizes);
+    let area: f64 = rects.iter().map(|r| r.area()).sum();
+    assert!((area - 24.0).abs() < 1e-9);
+
+    for (r, &size) in rects.iter().zip(&sizes) {
+        assert!((r.area() - size as f64).abs() < 1e-9, "{:?}", rects);
+        assert!(r.x >= 10.0 && r.x + r.width <= 16.0 + 1e-9, "{:?}", r);
+        assert!(r.y >= 20.0 && r.y + r.height <= 24.0 + 1e-9, "{:?}", r);
+    }
+}
+
+#[test]
+fn squarify_keeps_input_order() {
+    let rects = squarify(rect(0.0, 0.0, 10.0, 10.0), &[25, 75]);
+    assert_close(
+        &rects,
+        &[rect(7.5, 0.0, 2.5, 10.0), rect(0.0, 0.0, 7.5, 10.0)],
+    );
+}
+
+#[test]
+fn squarify_avoids_slivers() {
+    let sizes = [1; 64];
+    let rects = squarify(rect(0.0, 0.0, 800.0, 800.0), &sizes);
+    for r in &rects {
+        let ratio = f64::max(r.width / r.height, r.height / r.width);
+        assert!(ratio < 2.0, "{:?}", r);
+    }
+
+    let binary = binary_split(rect(0.0, 0.0, 800.0, 600.0), &[100, 1, 1, 1]);
+    let ratio = |r: &Rectangle| f64::max(r.width / r.height, r.height / r.width);
+    let squarified = squarify(rect(0.0, 0.0, 800.0, 600.0), &[100, 1, 1, 1]);
+    let worst = |rects: &[Rectangle]| rects.iter().map(ratio).fold(0.0, f64::max);
+    assert!(worst(&squarified) < worst(&binary));
+}
+
+#[test]
+fn squarify_gives_empty_items_no_space() {
+    let rects = squarify(rect(0.0, 0.0, 10.0, 10.0), &[0, 10]);
+    assert_eq!(rects[0].area(), 0.0);
+    assert_close(&rects[1..], &[rect(0.0, 0.0, 10.0, 10.0)]);
+}
+
+#[test]
+fn binary_split_halves_by_count() {
+    let rects = binary_split(rect(0.0, 0.0, 10.0, 10.0), &[1, 1, 2]);
+    assert_close(
+        &rects,
+        &[
+            rect(0.0, 0.0, 2.5, 10.0),
+            rect(2.5, 0.0, 7.5, 10.0 / 3.0),
+            rect(2.5, 10.0 / 3.0, 7.5, 20.0 / 3.0),
+        ],
+    );
+}
+
+#[test]
+fn frame_has_header_when_there_is_room() {
+    let frame = Frame::new(rect(0.0, 0.0, 100.0, 100.0));
+    assert_eq!(frame.header, Some(rect(2.0, 2.0, 96.0, HEADER_HEIGHT)));
+    assert_eq!(
+        frame.content,
+        rect(2.0, 2.0 + HEADER_HEIGHT, 96.0, 96.0 - HEADER_HEIGHT)
+    );
+
+    let frame = Frame::new(rect(0.0, 0.0, 100.0, 30.0));
+    assert_eq!(frame.header, None);
+    assert_eq!(frame.content, rect(2.0, 2.0, 96.0, 26.0));
+}
+
+#[test]
+fn nearest_in_direction_prefers_straight_ahead() {
+    // a 3x3 grid of unit squares
+    let grid: Vec<_> = (0..9)
+        .map(|i| rect((i % 3) as f64, (i / 3) as f64, 1.0, 1.0))
+        .collect();
+    let middle = grid[4];
+    assert_eq!(nearest_in_direction(middle, &grid, (1.0, 0.0)), Some(5));
+    assert_eq!(nearest_in_direction(middle, &grid, (-1.0, 0.0)), Some(3));
+    assert_eq!(nearest_in_direction(middle, &grid, (0.0, -1.0)), Some(1));
+    assert_eq!(nearest_in_direction(grid[0], &grid, (0.0, 1.0)), Some(3));
+    assert_eq!(nearest_in_direction(grid[2], &grid, (1.0, 0.0)), None);
+}
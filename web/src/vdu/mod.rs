@@ -1,210 +1,752 @@
 // copyright 2021 Remi Bernotavicius
 
-use colors::COLOR_NAMES;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use vdu_path_tree::{PathTree, PathTreeNode};
+use crate::tooltip;
+use bytesize::ByteSize;
+pub use colors::ColorScheme;
+use colors::{Coloring, Entry};
+pub use layout::Layout;
+use layout::{nearest_in_direction, Frame, Rectangle};
+use search::SearchResults;
+use std::collections::HashSet;
+use std::path::Path;
+use vdu_path_tree::{PathTree, PathTreeNode, SearchResponse, Subtree};
+use viewport::Viewport;
 use wasm_bindgen::prelude::*;
 
 mod colors;
+mod layout;
+mod search;
+mod viewport;
 
-struct Rectangle {
-    x: f64,
-    y: f64,
-    width: f64,
-    height: f64,
-}
+/// Directories drawn smaller than this are drawn as one rectangle instead of being divided up.
+const MIN_DIVIDED_AREA: f64 = 10_000.0;
+
+const LABEL_FONT: &str = "12px sans-serif";
+const LINE_HEIGHT: f64 = 14.0;
+/// Space between a leaf's edge and its label.
+const LABEL_PADDING: f64 = 3.0;
+const FRAME_COLOR: &str = "#505050";
+const HIGHLIGHT_COLOR: &str = "#ffcc00";
+const SEARCH_MATCH_COLOR: &str = "#ff00ff";
+const SELECTED_COLOR: &str = "#1e90ff";
+/// Drawn over everything not matching a search.
+const DIM_COLOR: &str = "rgba(255, 255, 255, 0.75)";
 
-impl Rectangle {
-    fn divide(&self, direction: Direction, left_percent: f64) -> (Self, Self) {
-        let right_percent = 1.0 - left_percent;
-        match direction {
-            Direction::Vertical => (
-                Self {
-                    x: self.x,
-                    y: self.y,
-                    width: self.width * left_percent,
-                    height: self.height,
-                },
-                Self {
-                    x: self.x + self.width * left_percent,
-                    y: self.y,
-                    width: self.width * right_percent,
-                    height: self.height,
-                },
-            ),
-            Direction::Horizontal => (
-                Self {
-                    x: self.x,
-                    y: self.y,
-                    width: self.width,
-                    height: self.height * left_percent,
-                },
-                Self {
-                    x: self.x,
-                    y: self.y + self.height * left_percent,
-                    width: self.width,
-                    height: self.height * right_percent,
-                },
-            ),
-        }
-    }
-
-    fn contains(&self, x: f64, y: f64) -> bool {
-        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
-    }
-
-    fn area(&self) -> f64 {
-        self.width * self.height
+/// Shortens `text` with an ellipsis until `measure` says it fits in `max_width`. Returns `None`
+/// if not even the first character fits.
+fn fit_text(text: &str, max_width: f64, measure: impl Fn(&str) -> f64) -> Option<String> {
+    if measure(text) <= max_width {
+        return Some(text.into());
     }
-}
 
-/// chooses a color based on the hash of the input
-fn color<T: Hash>(t: T) -> &'static str {
-    let mut s = DefaultHasher::new();
-    t.hash(&mut s);
-    COLOR_NAMES[(s.finish() as usize) % COLOR_NAMES.len()]
+    let chars: Vec<char> = text.chars().collect();
+    let shortened = |n: usize| format!("{}…", chars[..n].iter().collect::<String>());
+
+    // binary search for the longest prefix that fits
+    let (mut fits, mut too_long) = (0, chars.len());
+    while too_long - fits > 1 {
+        let mid = (fits + too_long) / 2;
+        if measure(&shortened(mid)) <= max_width {
+            fits = mid;
+        } else {
+            too_long = mid;
+        }
+    }
+    (fits > 0).then(|| shortened(fits))
 }
 
 #[test]
-fn color_same_for_same_input() {
-    let color1 = color(1);
-    let color2 = color(1);
-    assert_eq!(color1, color2);
+fn fit_text_truncates_with_ellipsis() {
+    let measure = |t: &str| t.chars().count() as f64 * 10.0;
+    assert_eq!(fit_text("hello", 50.0, measure), Some("hello".into()));
+    assert_eq!(fit_text("hello", 49.0, measure), Some("hel…".into()));
+    assert_eq!(fit_text("hello", 20.0, measure), Some("h…".into()));
+    assert_eq!(fit_text("hello", 15.0, measure), None);
+}
+
+/// A rectangle in the treemap, worked out when the view or the canvas changes and drawn until
+/// they change again.
+struct Cell {
+    rect: Rectangle,
+    /// The path from the root of the tree, like `/usr/share`.
+    path: String,
+    num_bytes: u64,
+    modified: Option<u64>,
+    is_directory: bool,
+    /// Set when the cell is drawn as a frame around its children, which follow it.
+    frame: Option<Frame>,
+    /// One past the index of the cell's last descendant, so the cells form a tree that can be
+    /// searched without looking at the descendants of cells that don't contain the point.
+    end: usize,
+}
+
+/// The index of the innermost of `cells` containing (x, y).
+fn innermost_cell_at(cells: &[Cell], x: f64, y: f64) -> Option<usize> {
+    let mut found = None;
+    let (mut i, mut end) = (0, cells.len());
+    while i < end {
+        let cell = &cells[i];
+        if cell.rect.contains(x, y) {
+            // look among its children next
+            found = Some(i);
+            end = cell.end;
+            i += 1;
+        } else {
+            // skip to its next sibling
+            i = cell.end;
+        }
+    }
+    found
+}
+
+impl Cell {
+    fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
+    }
+
+    /// The names leading from the root of the tree to the cell.
+    fn names(&self) -> Vec<String> {
+        self.path.split('/').skip(1).map(String::from).collect()
+    }
 }
 
 #[test]
-fn color_different_for_different_input() {
-    let color1 = color(1);
-    let color2 = color(2);
-    assert_ne!(color1, color2);
+fn innermost_cell_at_descends_into_containing_cells() {
+    let cell = |x, y, size, end| Cell {
+        rect: Rectangle {
+            x,
+            y,
+            width: size,
+            height: size,
+        },
+        path: String::new(),
+        num_bytes: 0,
+        modified: None,
+        is_directory: false,
+        frame: None,
+        end,
+    };
+    // two directories side by side, each with a child
+    let cells = [
+        cell(0.0, 0.0, 10.0, 2),
+        cell(1.0, 1.0, 5.0, 2),
+        cell(20.0, 0.0, 10.0, 4),
+        cell(21.0, 1.0, 5.0, 4),
+    ];
+    assert_eq!(innermost_cell_at(&cells, 2.0, 2.0), Some(1));
+    assert_eq!(innermost_cell_at(&cells, 8.0, 8.0), Some(0));
+    assert_eq!(innermost_cell_at(&cells, 22.0, 2.0), Some(3));
+    assert_eq!(innermost_cell_at(&cells, 28.0, 8.0), Some(2));
+    assert_eq!(innermost_cell_at(&cells, 15.0, 5.0), None);
 }
 
 pub struct Vdu {
     drawing_context: web_sys::CanvasRenderingContext2d,
-    canvas: web_sys::HtmlCanvasElement,
+    /// The size of the canvas in CSS pixels, which everything is laid out in.
+    size: (f64, f64),
+    /// How many of the canvas's pixels make up a CSS pixel.
+    pixel_ratio: f64,
     tree: PathTree,
     mouse_pos: (f64, f64),
+    layout: Layout,
+    coloring: Coloring,
+    /// The names leading from the root of the tree to the directory being shown.
+    view: Vec<String>,
+    /// The rectangles of the view, each followed by its descendants.
+    cells: Vec<Cell>,
+    /// Set when what is on the canvas is out of date.
+    dirty: bool,
+    /// The child of the view picked out from outside the treemap, like from the sidebar.
+    highlighted: Option<String>,
+    search: Option<SearchResults>,
+    /// The child of the view selected with the keyboard.
+    selected: Option<String>,
+    viewport: Viewport,
+    /// Set by a relayout, until the directories in view without their contents are looked for.
+    check_unloaded: bool,
+    /// The paths of the directories being loaded.
+    requested: HashSet<String>,
+    /// Counts the trees swapped in by `set_tree`.
+    tree_generation: u32,
 }
 
-#[derive(Clone, Copy)]
-enum Direction {
-    Horizontal,
-    Vertical,
-}
+impl Vdu {
+    pub fn new(drawing_context: web_sys::CanvasRenderingContext2d, tree: PathTree) -> Self {
+        let mut top_level: Vec<_> = tree.children().collect();
+        top_level.sort_by_key(|(_, node)| std::cmp::Reverse(node.num_bytes()));
+        let coloring = Coloring {
+            scheme: ColorScheme::default(),
+            now: (js_sys::Date::now() / 1000.0) as u64,
+            top_level: top_level
+                .into_iter()
+                .take(Coloring::max_top_level())
+                .map(|(name, _)| name.to_owned())
+                .collect(),
+        };
 
-impl Direction {
-    fn next(&self) -> Self {
-        match self {
-            Self::Horizontal => Self::Vertical,
-            Self::Vertical => Self::Horizontal,
-        }
+        let mut vdu = Self {
+            drawing_context,
+            size: (0.0, 0.0),
+            pixel_ratio: 1.0,
+            tree,
+            mouse_pos: (0.0, 0.0),
+            layout: Layout::default(),
+            coloring,
+            view: vec![],
+            cells: vec![],
+            dirty: true,
+            highlighted: None,
+            search: None,
+            selected: None,
+            viewport: Viewport::default(),
+            check_unloaded: true,
+            requested: HashSet::new(),
+            tree_generation: 0,
+        };
+        vdu.relayout();
+        vdu
     }
-}
 
-fn divide<'a>(
-    rect: Rectangle,
-    nodes: Vec<(&'a str, &'a PathTreeNode)>,
-    direction: Direction,
-) -> Vec<(Rectangle, (&'a str, &'a PathTreeNode))> {
-    if nodes.len() == 1 {
-        return vec![(rect, (&nodes[0].0, &nodes[0].1))];
+    pub fn root_name(&self) -> String {
+        match self.tree.root() {
+            Some(root) if !root.path().as_os_str().is_empty() => root.path().display().to_string(),
+            _ => "(all)".into(),
+        }
     }
 
-    let mut left_nodes = nodes;
-    let right_nodes = left_nodes.split_off(left_nodes.len() / 2);
-
-    let left_sum: u64 = left_nodes.iter().map(|(_, n)| n.num_bytes()).sum();
-    let right_sum: u64 = right_nodes.iter().map(|(_, n)| n.num_bytes()).sum();
-    let total = left_sum + right_sum;
-    let left_percent = left_sum as f64 / total as f64;
+    pub fn view(&self) -> &[String] {
+        &self.view
+    }
 
-    let (left_rect, right_rect) = rect.divide(direction, left_percent);
+    fn node_at<'a>(&'a self, names: &[impl AsRef<str>]) -> Option<&'a PathTreeNode> {
+        let root = self.tree.root()?;
+        let path = names
+            .iter()
+            .fold(root.path().to_owned(), |p, n| p.join(n.as_ref()));
+        self.tree.get(&path)
+    }
 
-    let left = divide(left_rect, left_nodes, direction.next());
-    let right = divide(right_rect, right_nodes, direction.next());
+    pub fn view_node(&self) -> Option<&PathTreeNode> {
+        self.node_at(&self.view)
+    }
 
-    let mut nodes = left;
-    nodes.extend(right);
-    nodes
-}
+    /// The path of the view, in the same form as the paths of cells.
+    pub fn view_path(&self) -> String {
+        self.view.iter().map(|n| format!("/{}", n)).collect()
+    }
 
-impl Vdu {
-    pub fn new(
-        drawing_context: web_sys::CanvasRenderingContext2d,
-        canvas: web_sys::HtmlCanvasElement,
-        tree: PathTree,
-    ) -> Self {
-        Self {
-            drawing_context,
-            canvas,
-            tree,
-            mouse_pos: (0.0, 0.0),
+    /// Shows the directory at `names`, or the deepest directory along the way that exists.
+    pub fn set_view(&mut self, mut names: Vec<String>) {
+        while !names.is_empty() && !self.node_at(&names).is_some_and(PathTreeNode::is_directory) {
+            names.pop();
         }
+        // going up selects the directory that was being viewed
+        self.selected = if names.len() < self.view.len() && self.view.starts_with(&names) {
+            Some(self.view[names.len()].clone())
+        } else {
+            None
+        };
+        self.view = names;
+        self.highlighted = None;
+        self.relayout();
     }
 
-    fn width(&self) -> u32 {
-        self.canvas.width()
+    fn view_rect(&self) -> Rectangle {
+        Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: self.size.0,
+            height: self.size.1,
+        }
     }
 
-    fn height(&self) -> u32 {
-        self.canvas.height()
+    /// Whether `node` is drawn as a frame around its children rather than as one rectangle.
+    fn is_divided(rect: Rectangle, node: &PathTreeNode) -> bool {
+        node.children().next().is_some() && rect.area() >= MIN_DIVIDED_AREA
     }
 
-    fn render_helper<'a>(
+    /// Lays out the children of `node` in `content`, adding them and their descendants to `cells`.
+    fn layout_cells(
         &self,
-        rect: Rectangle,
+        content: Rectangle,
+        node: &PathTreeNode,
         path: &str,
-        iter: impl Iterator<Item = (&'a str, &'a PathTreeNode)>,
-        selected: &mut Option<String>,
+        cells: &mut Vec<Cell>,
     ) {
-        let children: Vec<_> = iter.collect();
-        if children.is_empty() || rect.area() < 10_000.0 {
-            self.drawing_context
-                .set_fill_style(&JsValue::from_str(color(path)));
-            self.drawing_context
-                .fill_rect(rect.x, rect.y, rect.width, rect.height);
-            if rect.contains(self.mouse_pos.0, self.mouse_pos.1) {
-                self.drawing_context
-                    .set_fill_style(&JsValue::from_str("black"));
-                self.drawing_context
-                    .stroke_rect(rect.x, rect.y, rect.width, rect.height);
-                *selected = Some(path.into());
+        let children: Vec<_> = node.children().collect();
+        let sizes: Vec<_> = children.iter().map(|(_, n)| n.num_bytes()).collect();
+        let rects = self.layout.divide(content, &sizes);
+
+        for (rect, (name, child)) in rects.into_iter().zip(children) {
+            if rect.area() <= 0.0 {
+                continue;
             }
-        } else {
-            for (new_rect, (name, node)) in divide(rect, children, Direction::Vertical) {
-                let path = format!("{}/{}", path, name);
-                self.render_helper(new_rect, &path, node.children(), selected);
+            let path = format!("{}/{}", path, name);
+            let metadata = child.metadata();
+            let frame = Self::is_divided(rect, child).then(|| Frame::new(rect));
+            cells.push(Cell {
+                rect,
+                path: path.clone(),
+                num_bytes: child.num_bytes(),
+                modified: metadata.modified,
+                is_directory: child.is_directory(),
+                frame,
+                end: 0,
+            });
+            let index = cells.len() - 1;
+            if let Some(frame) = frame {
+                self.layout_cells(frame.content, child, &path, cells);
+            }
+            cells[index].end = cells.len();
+        }
+    }
+
+    /// Works out the rectangles again after the view, the layout or the canvas size changes.
+    fn relayout(&mut self) {
+        let mut cells = vec![];
+        if let Some(view_node) = self.view_node() {
+            self.layout_cells(self.view_rect(), view_node, &self.view_path(), &mut cells);
+        }
+        self.cells = cells;
+        self.viewport = Viewport::default();
+        self.check_unloaded = true;
+        self.dirty = true;
+    }
+
+    /// The paths of the directories that would be divided, or are being viewed, but whose
+    /// contents haven't been loaded, leaving out any already being loaded. They are then counted
+    /// as being loaded until `load_subtree` or `subtree_failed` is called for them.
+    pub fn take_unloaded(&mut self) -> Vec<String> {
+        if !std::mem::take(&mut self.check_unloaded) {
+            return vec![];
+        }
+
+        let view = self
+            .view_node()
+            .filter(|n| !n.is_loaded())
+            .map(|_| self.view_path());
+        let cells = self
+            .cells
+            .iter()
+            .filter(|c| c.frame.is_none() && c.is_directory && c.rect.area() >= MIN_DIVIDED_AREA)
+            .filter(|c| self.node_at(&c.names()).is_some_and(|n| !n.is_loaded()))
+            .map(|c| c.path.clone());
+        let unloaded: Vec<_> = view
+            .into_iter()
+            .chain(cells)
+            .filter(|p| !self.requested.contains(p))
+            .collect();
+        self.requested.extend(unloaded.iter().cloned());
+        unloaded
+    }
+
+    /// Fills in the contents of the directory at `path`.
+    pub fn load_subtree(&mut self, path: &str, subtree: Subtree) {
+        self.requested.remove(path);
+        let root = match self.tree.root() {
+            Some(root) => root.path().to_owned(),
+            None => return,
+        };
+        let mut names: Vec<_> = path.split('/').skip(1).collect();
+        let parent = match names.pop() {
+            Some(_) => names.iter().fold(root, |p, n| p.join(n)),
+            // the root's name is its whole path
+            None => Path::new("").to_owned(),
+        };
+        self.tree
+            .load_subtree(PathTree::from_subtree(&parent, subtree));
+
+        let viewport = self.viewport;
+        self.relayout();
+        self.viewport = viewport;
+    }
+
+    /// Replaces the tree with one from after it was scanned again, staying in the same directory
+    /// if it is still there.
+    pub fn set_tree(&mut self, tree: PathTree) {
+        self.tree = tree;
+        self.tree_generation = self.tree_generation.wrapping_add(1);
+        self.requested.clear();
+        let view = self.view.clone();
+        self.set_view(view);
+    }
+
+    /// Takes the entry at `names` out of the tree once it has been deleted, so the totals shrink
+    /// straight away.
+    pub fn remove_entry(&mut self, names: &[String]) {
+        let path = match self.node_at(names) {
+            Some(node) => node.path().to_owned(),
+            None => return,
+        };
+        self.tree.remove_path(&path);
+        self.selected = None;
+        self.highlighted = None;
+
+        let viewport = self.viewport;
+        self.relayout();
+        self.viewport = viewport;
+    }
+
+    /// Changes whenever the tree is replaced, so contents loaded for the old tree can be told
+    /// apart.
+    pub fn tree_generation(&self) -> u32 {
+        self.tree_generation
+    }
+
+    /// Lets the directory at `path` be asked for again after loading it failed.
+    pub fn subtree_failed(&mut self, path: &str) {
+        self.requested.remove(path);
+    }
+
+    /// The index of the innermost cell drawn at (x, y).
+    fn cell_index_at(&self, x: f64, y: f64) -> Option<usize> {
+        let (x, y) = self.viewport.to_layout((x, y));
+        innermost_cell_at(&self.cells, x, y)
+    }
+
+    fn cell_at(&self, x: f64, y: f64) -> Option<&Cell> {
+        Some(&self.cells[self.cell_index_at(x, y)?])
+    }
+
+    /// The cells of the children of the view, skipping over their descendants.
+    fn view_child_cells(&self) -> impl Iterator<Item = &Cell> {
+        let mut i = 0;
+        std::iter::from_fn(move || {
+            let cell = self.cells.get(i)?;
+            i = cell.end;
+            Some(cell)
+        })
+    }
+
+    /// The name of the child of the view under the mouse.
+    pub fn hovered_child(&self) -> Option<String> {
+        let (x, y) = self.mouse_pos;
+        self.cell_at(x, y)?.names().into_iter().nth(self.view.len())
+    }
+
+    /// Highlights what a search found, or stops highlighting search results when it is `None`.
+    pub fn set_search(&mut self, response: Option<SearchResponse>) {
+        self.search = response.map(SearchResults::new);
+        self.dirty = true;
+    }
+
+    /// How many search results there are, and how big they are.
+    pub fn search_summary(&self) -> Option<String> {
+        self.search.as_ref().map(|s| s.summary())
+    }
+
+    /// Moves to the next or previous search result, returning the view to show it in.
+    pub fn step_search(&mut self, delta: isize) -> Option<Vec<String>> {
+        let current = self.search.as_mut()?.step(delta)?;
+        let mut names: Vec<_> = current.split('/').skip(1).map(String::from).collect();
+        names.pop();
+        self.dirty = true;
+        Some(names)
+    }
+
+    /// The names leading from the root of the tree to the selected entry.
+    pub fn selected_names(&self) -> Option<Vec<String>> {
+        let mut names = self.view.clone();
+        names.push(self.selected.clone()?);
+        Some(names)
+    }
+
+    /// Where the selected entry is on disk.
+    pub fn selected_path(&self) -> Option<String> {
+        let node = self.node_at(&self.selected_names()?)?;
+        Some(node.path().display().to_string())
+    }
+
+    pub fn selected_is_directory(&self) -> bool {
+        self.selected_names()
+            .is_some_and(|names| self.node_at(&names).is_some_and(PathTreeNode::is_directory))
+    }
+
+    pub fn select_largest(&mut self) {
+        let largest = self.view_child_cells().max_by_key(|c| c.num_bytes);
+        if let Some(largest) = largest {
+            self.selected = Some(largest.name().to_owned());
+            self.dirty = true;
+        }
+    }
+
+    /// Moves the selection to the nearest child of the view in the direction (dx, dy), or selects
+    /// the largest child if nothing is selected.
+    pub fn move_selection(&mut self, direction: (f64, f64)) {
+        let cells: Vec<_> = self.view_child_cells().collect();
+        let current = match &self.selected {
+            Some(name) => cells.iter().find(|c| c.name() == name),
+            None => None,
+        };
+        let current = match current {
+            Some(current) => current,
+            None => return self.select_largest(),
+        };
+
+        let rects: Vec<_> = cells.iter().map(|c| c.rect).collect();
+        if let Some(i) = nearest_in_direction(current.rect, &rects, direction) {
+            self.selected = Some(cells[i].name().to_owned());
+            self.dirty = true;
+        }
+    }
+
+    /// Picks out the child of the view with the given name.
+    pub fn set_highlighted(&mut self, name: Option<String>) {
+        if name != self.highlighted {
+            self.highlighted = name;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns the view to zoom to when (x, y) is clicked: the clicked directory, or the
+    /// directory containing the clicked file.
+    pub fn on_click(&self, x: f64, y: f64) -> Option<Vec<String>> {
+        let mut new_view = self.cell_at(x, y)?.names();
+        if !self
+            .node_at(&new_view)
+            .is_some_and(PathTreeNode::is_directory)
+        {
+            new_view.pop();
+        }
+        (new_view != self.view).then_some(new_view)
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.relayout();
+    }
+
+    pub fn set_color_scheme(&mut self, scheme: ColorScheme) {
+        self.coloring.scheme = scheme;
+        self.dirty = true;
+    }
+
+    /// What each color means in the current color scheme.
+    pub fn legend(&self) -> Vec<(String, &'static str)> {
+        self.coloring.legend()
+    }
+
+    /// Called after the canvas changes size, given in CSS pixels along with how many of the
+    /// canvas's pixels there are to each of them.
+    pub fn on_resize(&mut self, width: f64, height: f64, pixel_ratio: f64) {
+        self.size = (width, height);
+        self.pixel_ratio = pixel_ratio;
+        self.relayout();
+    }
+
+    /// Magnifies the treemap by `factor` around (x, y) on the canvas.
+    pub fn zoom_at(&mut self, factor: f64, x: f64, y: f64) {
+        self.viewport.zoom_at(factor, (x, y), self.size);
+        self.dirty = true;
+    }
+
+    /// Moves the magnified treemap by (dx, dy).
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.viewport.pan((dx, dy), self.size);
+        self.dirty = true;
+    }
+
+    pub fn is_magnified(&self) -> bool {
+        self.viewport != Viewport::default()
+    }
+
+    /// Selects the child of the view drawn at (x, y) on the canvas.
+    pub fn select_at(&mut self, x: f64, y: f64) {
+        let name = self
+            .cell_at(x, y)
+            .and_then(|c| c.names().into_iter().nth(self.view.len()));
+        if name.is_some() {
+            self.selected = name;
+            self.dirty = true;
+        }
+    }
+
+    /// Draws `lines` of text from the top of `rect`, leaving out lines that don't fit and
+    /// truncating ones that are too wide.
+    fn draw_label(&self, rect: Rectangle, lines: &[String], color: &str) {
+        let context = &self.drawing_context;
+        context.save();
+        context.begin_path();
+        context.rect(rect.x, rect.y, rect.width, rect.height);
+        context.clip();
+        context.set_font(LABEL_FONT);
+        context.set_text_baseline("top");
+        context.set_fill_style(&JsValue::from_str(color));
+
+        let measure = |t: &str| context.measure_text(t).map_or(f64::MAX, |m| m.width());
+        for (i, line) in lines.iter().enumerate() {
+            let y = rect.y + i as f64 * LINE_HEIGHT;
+            if y + LINE_HEIGHT > rect.y + rect.height {
+                break;
+            }
+            if let Some(text) = fit_text(line, rect.width, measure) {
+                context.fill_text(&text, rect.x, y).unwrap();
             }
         }
+        context.restore();
     }
 
-    pub fn render(&self) {
+    fn render_leaf(&self, cell: &Cell) {
+        let context = &self.drawing_context;
+        let rect = cell.rect;
+        let entry = Entry {
+            path: &cell.path,
+            is_directory: cell.is_directory,
+            modified: cell.modified,
+        };
+        context.set_fill_style(&JsValue::from_str(self.coloring.color(&entry)));
+        context.fill_rect(rect.x, rect.y, rect.width, rect.height);
+        context.set_line_width(0.5);
+        context.set_stroke_style(&JsValue::from_str("rgba(0, 0, 0, 0.4)"));
+        context.stroke_rect(rect.x, rect.y, rect.width, rect.height);
+
+        // the label gets a white outline so it can be read on dark colors too
+        context.save();
+        context.set_shadow_color("white");
+        context.set_shadow_blur(2.0);
+        let lines = [
+            cell.name().to_owned(),
+            ByteSize::b(cell.num_bytes).to_string(),
+        ];
+        self.draw_label(rect.inset(LABEL_PADDING), &lines, "black");
+        context.restore();
+    }
+
+    fn render_directory(&self, cell: &Cell, frame: &Frame) {
+        let context = &self.drawing_context;
+        let rect = cell.rect;
+        context.set_fill_style(&JsValue::from_str(FRAME_COLOR));
+        context.fill_rect(rect.x, rect.y, rect.width, rect.height);
+
+        if let Some(header) = frame.header {
+            let text = format!("{}  {}", cell.name(), ByteSize::b(cell.num_bytes));
+            let label_rect = Rectangle {
+                x: header.x + LABEL_PADDING,
+                width: header.width - 2.0 * LABEL_PADDING,
+                ..header
+            };
+            self.draw_label(label_rect, &[text], "white");
+        }
+    }
+
+    /// Draws the treemap if anything has changed since it was last drawn.
+    pub fn render(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        // resizing the canvas resets the transform, so it is set every time
+        let ratio = self.pixel_ratio;
         self.drawing_context
-            .set_fill_style(&JsValue::from_str("white"));
+            .set_transform(ratio, 0.0, 0.0, ratio, 0.0, 0.0)
+            .unwrap();
         self.drawing_context
-            .clear_rect(0.0, 0.0, self.width() as f64, self.height() as f64);
+            .clear_rect(0.0, 0.0, self.size.0, self.size.1);
 
-        let mut selected = None;
-        let starting_rect = Rectangle {
-            x: 0.0,
-            y: 0.0,
-            width: self.width() as f64,
-            height: self.height() as f64 - 20.0,
-        };
-        self.render_helper(starting_rect, "", self.tree.children(), &mut selected);
+        let Viewport { scale, offset } = self.viewport;
+        self.drawing_context
+            .set_transform(
+                ratio * scale,
+                0.0,
+                0.0,
+                ratio * scale,
+                ratio * offset.0,
+                ratio * offset.1,
+            )
+            .unwrap();
+
+        let context = &self.drawing_context;
+        for cell in &self.cells {
+            match &cell.frame {
+                Some(frame) => self.render_directory(cell, frame),
+                None => self.render_leaf(cell),
+            }
 
-        if let Some(selected) = selected {
+            // a directory is dimmed before its children are drawn over it, so they can stand out
+            if self
+                .search
+                .as_ref()
+                .is_some_and(|s| !s.is_relevant(&cell.path))
+            {
+                let rect = cell.rect;
+                context.set_fill_style(&JsValue::from_str(DIM_COLOR));
+                context.fill_rect(rect.x, rect.y, rect.width, rect.height);
+            }
+        }
+
+        if let Some(search) = &self.search {
+            context.set_stroke_style(&JsValue::from_str(SEARCH_MATCH_COLOR));
+            for cell in self.cells.iter().filter(|c| search.is_match(&c.path)) {
+                let is_current = search.current() == Some(&cell.path[..]);
+                let width = if is_current { 4.0 } else { 2.0 };
+                let rect = cell.rect.inset(width / 2.0);
+                context.set_line_width(width);
+                context.stroke_rect(rect.x, rect.y, rect.width, rect.height);
+            }
+        }
+
+        if let Some(cell) = self.cell_at(self.mouse_pos.0, self.mouse_pos.1) {
+            let rect = cell.rect;
+            self.drawing_context.set_line_width(1.0);
+            self.drawing_context
+                .set_stroke_style(&JsValue::from_str("black"));
+            self.drawing_context
+                .stroke_rect(rect.x, rect.y, rect.width, rect.height);
+        }
+
+        let selected = self
+            .selected
+            .as_ref()
+            .and_then(|name| self.view_child_cells().find(|c| c.name() == name));
+        if let Some(cell) = selected {
+            let rect = cell.rect.inset(1.5);
+            self.drawing_context.set_line_width(3.0);
             self.drawing_context
-                .set_fill_style(&JsValue::from_str("black"));
-            self.drawing_context.set_font("30px arial");
+                .set_stroke_style(&JsValue::from_str(SELECTED_COLOR));
             self.drawing_context
-                .fill_text(&selected[..], 0.0, self.height() as f64)
-                .unwrap();
+                .stroke_rect(rect.x, rect.y, rect.width, rect.height);
+        }
+
+        let highlighted = self
+            .highlighted
+            .as_ref()
+            .and_then(|name| self.view_child_cells().find(|c| c.name() == name));
+        if let Some(cell) = highlighted {
+            let rect = cell.rect.inset(1.5);
+            self.drawing_context.set_line_width(3.0);
+            self.drawing_context
+                .set_stroke_style(&JsValue::from_str(HIGHLIGHT_COLOR));
+            self.drawing_context
+                .stroke_rect(rect.x, rect.y, rect.width, rect.height);
         }
     }
 
     pub fn on_mouse_move(&mut self, x: f64, y: f64) {
+        let (old_x, old_y) = self.mouse_pos;
+        let hovered = self.cell_index_at(old_x, old_y);
         self.mouse_pos = (x, y);
+
+        // only the outline of the hovered cell depends on the mouse
+        if self.cell_index_at(x, y) != hovered {
+            self.dirty = true;
+        }
+    }
+
+    pub fn on_mouse_leave(&mut self) {
+        self.on_mouse_move(f64::NAN, f64::NAN);
+    }
+
+    /// The lines of the tooltip for whatever is under the mouse.
+    pub fn hover_details(&self) -> Option<Vec<String>> {
+        let (x, y) = self.mouse_pos;
+        let path = self.cell_at(x, y)?.names();
+        let node = self.node_at(&path)?;
+        let parent = self.node_at(&path[..path.len() - 1])?;
+        Some(tooltip::details(
+            node,
+            Some(parent.num_bytes()),
+            self.tree.num_bytes(),
+        ))
     }
 }
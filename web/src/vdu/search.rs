@@ -0,0 +1,142 @@
+// copyright 2021 Remi Bernotavicius
+
+use bytesize::ByteSize;
+use std::collections::HashSet;
+use vdu_path_tree::SearchResponse;
+
+/// `path` and the paths of the directories above it, excluding the root.
+fn self_and_ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/')
+        .skip(1)
+        .map(move |(i, _)| &path[..i])
+        .chain(Some(path))
+}
+
+/// What matched a search, and which of the matches is being looked at.
+pub struct SearchResults {
+    /// In path order.
+    matches: Vec<String>,
+    match_set: HashSet<String>,
+    /// The directories with matches inside them.
+    ancestors: HashSet<String>,
+    /// Can be more than the number of `matches` when the server sent only some of them.
+    num_matches: usize,
+    num_bytes: u64,
+    current: Option<usize>,
+}
+
+impl SearchResults {
+    pub fn new(response: SearchResponse) -> Self {
+        let matches = response.matches;
+        let mut ancestors = HashSet::new();
+        for path in &matches {
+            let mut paths: Vec<_> = self_and_ancestors(path).collect();
+            paths.pop();
+            ancestors.extend(paths.into_iter().map(String::from));
+        }
+
+        Self {
+            match_set: matches.iter().cloned().collect(),
+            matches,
+            ancestors,
+            num_matches: response.num_matches,
+            num_bytes: response.num_bytes,
+            current: None,
+        }
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.match_set.contains(path)
+    }
+
+    /// Whether `path` should stand out: it matched, is inside a match, or has a match inside it.
+    pub fn is_relevant(&self, path: &str) -> bool {
+        self.ancestors.contains(path) || self_and_ancestors(path).any(|p| self.is_match(p))
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.current.map(|i| &self.matches[i][..])
+    }
+
+    /// Moves `delta` matches forwards or backwards, wrapping around at the ends.
+    pub fn step(&mut self, delta: isize) -> Option<&str> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let len = self.matches.len() as isize;
+        let next = match self.current {
+            Some(i) => (i as isize + delta).rem_euclid(len),
+            None if delta < 0 => len - 1,
+            None => 0,
+        };
+        self.current = Some(next as usize);
+        self.current()
+    }
+
+    pub fn summary(&self) -> String {
+        let count = match (self.num_matches, self.current) {
+            (0, _) => return "no matches".into(),
+            (1, _) => "1 match".into(),
+            (n, Some(i)) => format!("{} of {} matches", i + 1, n),
+            (n, None) => format!("{} matches", n),
+        };
+        let count = if self.matches.len() < self.num_matches {
+            format!("{} (first {} shown)", count, self.matches.len())
+        } else {
+            count
+        };
+        format!("{}, {}", count, ByteSize::b(self.num_bytes))
+    }
+}
+
+#[cfg(test)]
+fn test_results(pattern: &str, max_matches: usize) -> SearchResults {
+    use std::path::Path;
+    use vdu_path_tree::{NodeMetadata, PathTree, Query, SearchMode};
+
+    let mut tree = PathTree::empty();
+    for &(path, num_bytes) in &[
+        ("/r", 0),
+        ("/r/a", 0),
+        ("/r/a/core.1", 100),
+        ("/r/a/b", 0),
+        ("/r/a/b/core.2", 50),
+        ("/r/c", 7),
+    ] {
+        tree.add_path(Path::new(path), num_bytes, NodeMetadata::unknown());
+    }
+    let query = Query::new(SearchMode::Glob, pattern).unwrap();
+    SearchResults::new(tree.search_response(&query, max_matches))
+}
+
+#[test]
+fn search_results_know_what_to_highlight() {
+    let results = test_results("core.*", 10);
+    assert!(results.is_match("/a/core.1"));
+    assert!(!results.is_match("/a"));
+    assert!(results.is_relevant("/a"));
+    assert!(results.is_relevant("/a/b/core.2"));
+    assert!(!results.is_relevant("/c"));
+    assert_eq!(results.summary(), "2 matches, 150 B");
+}
+
+#[test]
+fn search_results_step_wraps_around() {
+    let mut results = test_results("core.*", 10);
+    assert_eq!(results.step(-1), Some("/a/core.1"));
+    assert_eq!(results.step(1), Some("/a/b/core.2"));
+    assert_eq!(results.step(1), Some("/a/core.1"));
+    assert_eq!(results.summary(), "2 of 2 matches, 150 B");
+
+    let mut results = test_results("nothing", 10);
+    assert_eq!(results.step(1), None);
+    assert_eq!(results.summary(), "no matches");
+}
+
+#[test]
+fn search_results_say_when_some_were_left_out() {
+    let mut results = test_results("core.*", 1);
+    assert_eq!(results.step(1), Some("/a/b/core.2"));
+    assert_eq!(results.step(1), Some("/a/b/core.2"));
+    assert_eq!(results.summary(), "1 of 2 matches (first 1 shown), 150 B");
+}
@@ -0,0 +1,78 @@
+// copyright 2021 Remi Bernotavicius
+
+/// How far in the treemap can be magnified by pinching.
+const MAX_SCALE: f64 = 20.0;
+
+/// Magnification and panning of the drawn treemap, on top of its layout. A point in the layout
+/// is drawn at `point * scale + offset`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub scale: f64,
+    pub offset: (f64, f64),
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset: (0.0, 0.0),
+        }
+    }
+}
+
+impl Viewport {
+    /// Where the point (x, y) on the canvas is in the layout.
+    pub fn to_layout(self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            (x - self.offset.0) / self.scale,
+            (y - self.offset.1) / self.scale,
+        )
+    }
+
+    /// Keeps the layout covering the whole canvas of the given size.
+    fn clamp(&mut self, (width, height): (f64, f64)) {
+        self.scale = self.scale.clamp(1.0, MAX_SCALE);
+        self.offset.0 = self.offset.0.clamp(width * (1.0 - self.scale), 0.0);
+        self.offset.1 = self.offset.1.clamp(height * (1.0 - self.scale), 0.0);
+    }
+
+    /// Magnifies by `factor`, keeping what is at `center` on the canvas in place.
+    pub fn zoom_at(&mut self, factor: f64, center: (f64, f64), size: (f64, f64)) {
+        let (x, y) = self.to_layout(center);
+        self.scale *= factor;
+        self.offset = (center.0 - x * self.scale, center.1 - y * self.scale);
+        self.clamp(size);
+    }
+
+    pub fn pan(&mut self, (dx, dy): (f64, f64), size: (f64, f64)) {
+        self.offset.0 += dx;
+        self.offset.1 += dy;
+        self.clamp(size);
+    }
+}
+
+#[test]
+fn zoom_at_keeps_center_in_place() {
+    let size = (100.0, 100.0);
+    let mut viewport = Viewport::default();
+    viewport.zoom_at(2.0, (25.0, 50.0), size);
+    assert_eq!(viewport.scale, 2.0);
+    assert_eq!(viewport.to_layout((25.0, 50.0)), (25.0, 50.0));
+    assert_eq!(viewport.to_layout((0.0, 0.0)), (12.5, 25.0));
+
+    // zooming out past the whole treemap stops at it
+    viewport.zoom_at(0.1, (25.0, 50.0), size);
+    assert_eq!(viewport, Viewport::default());
+}
+
+#[test]
+fn pan_stays_over_the_treemap() {
+    let size = (100.0, 100.0);
+    let mut viewport = Viewport::default();
+    viewport.pan((10.0, 10.0), size);
+    assert_eq!(viewport, Viewport::default());
+
+    viewport.zoom_at(2.0, (0.0, 0.0), size);
+    viewport.pan((-30.0, -500.0), size);
+    assert_eq!(viewport.offset, (-30.0, -100.0));
+}